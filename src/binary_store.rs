@@ -0,0 +1,1334 @@
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use crate::storage::{
+    apply_journal, read_counter, read_metadata, read_state, recover_journal, set_meta_value, write_atomically,
+    write_metadata, write_state, Journal, LockFile, METADATA_FILE,
+};
+use crate::store::{parent_changed, Store};
+use crate::types::*;
+
+// Constants
+const DATA_FILE: &str = "funk_notes.bin";
+const INDEX_FILE: &str = "funk_notes.idx";
+const JOURNAL_FILE: &str = "funk_journal.bin";
+const DATA_MAGIC: &[u8; 8] = b"FUNKBIN1";
+const INDEX_MAGIC: &[u8; 8] = b"FUNKIDX1";
+const DATA_HEADER: u64 = 16;      // Magic, generation
+const INDEX_HEADER: u64 = 32;     // Magic, generation, number of ID entries, number of title entries
+const SLOT_HEADER: usize = 14;    // Capacity, length, checksum, kind, live
+const ENTRY_SIZE: u64 = 17;       // Both kinds of index entry
+
+
+// ====== THE BINARY STORE =====
+// The notes as records in a binary file, with an index file for finding them
+// without reading everything:
+//
+//   funk_notes.bin   header (magic, generation), then one slot per record:
+//                    capacity u32, length u32, checksum u32, kind u8, live u8,
+//                    the encoded record and room for it to grow
+//   funk_notes.idx   header (magic, generation, counts), then entries sorted
+//                    by ID (id u64, kind u8, slot offset u64) and entries
+//                    sorted by title hash (hash u64, kind u8, id u64)
+//
+// Lookups by ID or title are binary searches over the index file. A changed
+// record is rewritten in its slot if it still fits, otherwise it moves to the
+// end and the old slot is marked dead. Those writes go through
+// funk_journal.bin first (the same journal as the text store's), so a crash
+// part way through a change is finished on the next open. The index is
+// rewritten when a record is added, removed, moved or retitled; an edit that
+// stays in its slot only updates the index's generation. The generation ties
+// the index to the data file, and an index that doesn't match is rebuilt
+// when the store is opened. Whole-notebook saves rewrite both files compactly.
+//
+// The ID counter, navigation state and lock are shared with the text store
+// in funk_metadata.txt.
+
+/// The notes as binary records with an on-disk index.
+pub struct BinaryStore {
+    data_path: PathBuf,
+    index_path: PathBuf,
+    meta_path: PathBuf,
+    journal_path: PathBuf,
+    tx: Option<BinaryTx>,   // The change in progress, if any
+}
+
+// A record as it should be after a change, or None to remove it
+type Staged = (NodeKind, usize, Option<Stored>);
+
+// An index entry a change replaces: kind code, ID and the new entry, if any
+type IndexChange = (u8, usize, Option<Entry>);
+
+// A change in progress, held under the lock. Records are staged in memory and
+// only written on commit.
+struct BinaryTx {
+    last_id: usize,
+    allocated: bool,
+    rewrite: Option<Notebook>,                // A whole new notebook to write
+    staged: Vec<Staged>,                      // Single records
+    _lock: LockFile,
+}
+
+impl BinaryStore {
+    /// The binary store kept in the given directory, which is created if need be.
+    /// A change that was interrupted last time is finished or thrown away, and
+    /// an index left out of date by a crash is rebuilt.
+    pub fn open(dir: &Path) -> io::Result<BinaryStore> {
+        fs::create_dir_all(dir)?;
+        let store = BinaryStore {
+            data_path: dir.join(DATA_FILE),
+            index_path: dir.join(INDEX_FILE),
+            meta_path: dir.join(METADATA_FILE),
+            journal_path: dir.join(JOURNAL_FILE),
+            tx: None,
+        };
+        recover_journal(&store.journal_path, &store.data_path, &store.meta_path)?;
+
+        if store.data_path.exists() && !store.index_is_current()? {
+            let _lock = LockFile::acquire(&store.meta_path)?;
+            let (generation, slots) = store.read_slots()?;
+            let entries = slots.iter().filter(|s| s.live).filter_map(|s| s.record.entry(s.offset)).collect();
+            store.write_index(generation, entries)?;
+        }
+        Ok(store)
+    }
+
+    /// Whether the directory has a binary store in it.
+    pub fn exists(dir: &Path) -> bool {
+        dir.join(DATA_FILE).exists()
+    }
+
+    // Run a change inside the current transaction, or in one of its own
+    fn change(&mut self, f: impl FnOnce(&mut BinaryTx)) -> io::Result<()> {
+        if let Some(tx) = self.tx.as_mut() {
+            f(tx);
+            return Ok(());
+        }
+        self.begin()?;
+        f(self.tx.as_mut().expect("just begun"));
+        self.commit()
+    }
+
+    // Stage a record as it is in the notebook (or its removal, if it's gone)
+    fn stage(tx: &mut BinaryTx, notebook: &Notebook, kind: NodeKind, id: usize) {
+        tx.staged.push((kind, id, Stored::from_notebook(notebook, kind, id)));
+    }
+
+    // The newest version of a record: staged, in a pending rewrite, or on disk
+    fn load_stored(&mut self, kind: NodeKind, id: usize) -> io::Result<Option<Stored>> {
+        if let Some(tx) = &self.tx {
+            if let Some((_, _, record)) = tx.staged.iter().rev().find(|(k, i, _)| *k == kind && *i == id) {
+                return Ok(record.clone());
+            }
+            if let Some(notebook) = &tx.rewrite {
+                return Ok(Stored::from_notebook(notebook, kind, id));
+            }
+        }
+
+        let Some(entry) = self.find_id(id)? else {
+            return Ok(None);
+        };
+        if entry.kind != kind_code(kind) {
+            return Ok(None);
+        }
+        let mut file = File::open(&self.data_path)?;
+        let slot = read_slot(&mut file, entry.key)?;
+        Ok(Some(slot.record))
+    }
+
+    // ===== Reading the files =====
+    fn index_is_current(&self) -> io::Result<bool> {
+        let Ok(mut index) = File::open(&self.index_path) else {
+            return Ok(false);
+        };
+        let mut header = [0u8; INDEX_HEADER as usize];
+        if index.read_exact(&mut header).is_err() || &header[..8] != INDEX_MAGIC {
+            return Ok(false);
+        }
+        // A cut-off index has fewer entries than its header counts
+        if index_size(u64_at(&header, 16), u64_at(&header, 24)) != Some(index.metadata()?.len()) {
+            return Ok(false);
+        }
+        Ok(u64_at(&header, 8) == self.generation()?)
+    }
+
+    fn generation(&self) -> io::Result<u64> {
+        let mut header = [0u8; DATA_HEADER as usize];
+        File::open(&self.data_path)?.read_exact(&mut header)?;
+        if &header[..8] != DATA_MAGIC {
+            return Err(invalid(format!("{} isn't a funknotes binary store", DATA_FILE)));
+        }
+        Ok(u64_at(&header, 8))
+    }
+
+    // Every slot in the data file, in order
+    fn read_slots(&self) -> io::Result<(u64, Vec<Slot>)> {
+        if !self.data_path.exists() {
+            return Ok((0, Vec::new()));
+        }
+        let bytes = fs::read(&self.data_path)?;
+        if bytes.len() < DATA_HEADER as usize || &bytes[..8] != DATA_MAGIC {
+            return Err(invalid(format!("{} isn't a funknotes binary store", DATA_FILE)));
+        }
+
+        let mut slots = Vec::new();
+        let mut offset = DATA_HEADER as usize;
+        while offset < bytes.len() {
+            let slot = parse_slot(&bytes[offset..], offset as u64)?;
+            offset += SLOT_HEADER + slot.capacity as usize;
+            slots.push(slot);
+        }
+        Ok((u64_at(&bytes, 8), slots))
+    }
+
+    fn read_all(&self) -> io::Result<Notebook> {
+        let (_, slots) = self.read_slots()?;
+        let mut notebook = Notebook::default();
+        for slot in slots.into_iter().filter(|s| s.live) {
+            slot.record.put_in(&mut notebook);
+        }
+
+        // Records that grew were moved to the end, so go by ID for a stable order
+        notebook.notes.sort_by_key(|n| n.id);
+        notebook.objects.sort_by_key(|o| o.id);
+        notebook.items.sort_by_key(|i| i.id);
+        notebook.milestones.sort_by_key(|m| m.id);
+        Ok(notebook)
+    }
+
+    // The index file's ID entries (key = slot offset), each with its title's
+    // hash taken from the title entries
+    fn read_index(&self) -> io::Result<Vec<Entry>> {
+        let bytes = fs::read(&self.index_path)?;
+        if bytes.len() < INDEX_HEADER as usize
+            || index_size(u64_at(&bytes, 16), u64_at(&bytes, 24)) != Some(bytes.len() as u64)
+        {
+            return Err(invalid(format!("{} is damaged", INDEX_FILE)));
+        }
+        let (id_count, title_count) = (u64_at(&bytes, 16) as usize, u64_at(&bytes, 24) as usize);
+        let entry = |i: usize| Entry::from_bytes(&bytes[INDEX_HEADER as usize + i * ENTRY_SIZE as usize..]);
+
+        let mut ids: Vec<Entry> = (0..id_count).map(entry).collect();
+        for title in (id_count..id_count + title_count).map(entry) {
+            if let Ok(i) = ids.binary_search_by_key(&title.key, |e| e.value) {
+                ids[i].title = Some(title.value);
+            }
+        }
+        Ok(ids)
+    }
+
+    // Binary search of the ID entries, reading only the entries it looks at
+    fn find_id(&self, id: usize) -> io::Result<Option<Entry>> {
+        if !self.index_path.exists() {
+            return Ok(None);
+        }
+        let mut index = File::open(&self.index_path)?;
+        let (id_count, _) = index_counts(&mut index)?;
+        let found = search(&mut index, 0, id_count, |e| e.value.cmp(&(id as u64)))?;
+        Ok(found.filter(|e| e.value == id as u64))
+    }
+
+    // Every title entry with the title's hash, checked against the records
+    // themselves in case two titles hash the same
+    fn find_title(&self, kind: NodeKind, title: &str) -> io::Result<Vec<usize>> {
+        if !self.index_path.exists() {
+            return Ok(Vec::new());
+        }
+        let mut index = File::open(&self.index_path)?;
+        let (id_count, title_count) = index_counts(&mut index)?;
+        let hash = title_hash(title);
+
+        let mut first = id_count;
+        let mut last = id_count + title_count;
+        while first < last {
+            let mid = first + (last - first) / 2;
+            if read_entry(&mut index, mid)?.value < hash {
+                first = mid + 1;
+            } else {
+                last = mid;
+            }
+        }
+
+        let mut data = File::open(&self.data_path)?;
+        let mut ids = Vec::new();
+        for i in first..id_count + title_count {
+            let entry = read_entry(&mut index, i)?;
+            if entry.value != hash {
+                break;
+            }
+            if entry.kind != kind_code(kind) {
+                continue;
+            }
+            let Some(found) = self.find_id(entry.key as usize)? else {
+                continue;
+            };
+            if read_slot(&mut data, found.key)?.record.title() == Some(title) {
+                ids.push(entry.key as usize);
+            }
+        }
+        ids.sort_unstable();
+        Ok(ids)
+    }
+
+    // ===== Writing the files =====
+    // The whole notebook, compactly, replacing both files
+    fn write_all(&self, notebook: &Notebook) -> io::Result<()> {
+        let generation = if self.data_path.exists() { self.generation()? + 1 } else { 1 };
+        let mut bytes = DATA_MAGIC.to_vec();
+        bytes.extend_from_slice(&generation.to_le_bytes());
+
+        let records = notebook.notes.iter().cloned().map(Stored::Note)
+            .chain(notebook.objects.iter().cloned().map(Stored::Object))
+            .chain(notebook.items.iter().cloned().map(Stored::Item))
+            .chain(notebook.milestones.iter().cloned().map(Stored::Milestone))
+            .chain(notebook.trash.iter().cloned().map(Stored::Trash));
+
+        let mut entries = Vec::new();
+        for record in records {
+            let payload = record.encode();
+            entries.extend(record.entry(bytes.len() as u64));
+            bytes.extend(slot_bytes(room_for(payload.len()), record.code(), true, &payload));
+        }
+
+        write_atomically(&self.data_path, &bytes)?;
+        self.write_index(generation, entries)
+    }
+
+    // Single records: in place when they fit, otherwise on the end. Every
+    // write goes in one journal with the counter, so a crash leaves all of
+    // them done or none. The index is only rewritten if an entry in it changed.
+    fn write_staged(&self, staged: Vec<Staged>, last_id: Option<usize>) -> io::Result<()> {
+        if !self.data_path.exists() {
+            self.write_all(&Notebook::default())?;
+        }
+        let (journal, generation, changes) = self.plan_writes(staged, last_id)?;
+        apply_journal(&self.journal_path, &journal, &self.data_path, &self.meta_path)?;
+
+        if changes.is_empty() {
+            let mut index = OpenOptions::new().write(true).open(&self.index_path)?;
+            index.seek(SeekFrom::Start(8))?;
+            index.write_all(&generation.to_le_bytes())?;
+            return index.sync_all();
+        }
+        let mut ids = self.read_index()?;
+        for (code, id, entry) in changes {
+            ids.retain(|e| !(e.value == id as u64 && e.kind == code));
+            ids.extend(entry);
+        }
+        self.write_index(generation, ids)
+    }
+
+    // The journal for the staged records and the generation it leaves the
+    // data file at, with the index entries that change
+    fn plan_writes(&self, staged: Vec<Staged>, last_id: Option<usize>) -> io::Result<(Journal, u64, Vec<IndexChange>)> {
+        let mut file = File::open(&self.data_path)?;
+        let mut end = file.metadata()?.len();
+        let mut journal = Journal { last_id, ..Journal::default() };
+        let mut changes = Vec::new();
+
+        for (kind, id, record) in latest(staged) {
+            let slot = match self.find_id(id)?.filter(|e| e.kind == kind_code(kind)) {
+                Some(entry) => Some(read_slot(&mut file, entry.key)?),
+                None => None,
+            };
+
+            match (record, slot) {
+                (Some(record), Some(slot)) if record.encode().len() <= slot.capacity as usize => {
+                    journal.writes.push((slot.offset, slot_bytes(slot.capacity, record.code(), true, &record.encode())));
+                    if record.title() != slot.record.title() {
+                        changes.push((kind_code(kind), id, record.entry(slot.offset)));
+                    }
+                }
+                (record, slot) => {
+                    if let Some(slot) = slot {
+                        journal.writes.push(dead_flag(&slot));
+                    }
+                    let entry = match record {
+                        Some(record) => {
+                            let payload = record.encode();
+                            let bytes = slot_bytes(room_for(payload.len()), record.code(), true, &payload);
+                            let offset = end;
+                            end += bytes.len() as u64;
+                            journal.writes.push((offset, bytes));
+                            record.entry(offset)
+                        }
+                        None => None,
+                    };
+                    changes.push((kind_code(kind), id, entry));
+                }
+            }
+        }
+
+        let generation = self.generation()? + 1;
+        journal.writes.push((8, generation.to_le_bytes().to_vec()));
+        Ok((journal, generation, changes))
+    }
+
+    fn write_index(&self, generation: u64, entries: Vec<Entry>) -> io::Result<()> {
+        let mut ids = entries.clone();
+        ids.sort_by_key(|e| e.value);
+        let mut titles: Vec<Entry> = entries
+            .iter()
+            .filter_map(|e| e.title.map(|hash| Entry { value: hash, kind: e.kind, key: e.value, title: None }))
+            .collect();
+        titles.sort_by_key(|e| (e.value, e.key));
+
+        let mut bytes = INDEX_MAGIC.to_vec();
+        for n in [generation, ids.len() as u64, titles.len() as u64] {
+            bytes.extend_from_slice(&n.to_le_bytes());
+        }
+        for entry in ids.iter().chain(&titles) {
+            entry.push_bytes(&mut bytes);
+        }
+        write_atomically(&self.index_path, &bytes)
+    }
+}
+
+impl Store for BinaryStore {
+    fn load_notebook(&mut self) -> io::Result<Notebook> {
+        let mut notebook = match self.tx.as_ref().and_then(|tx| tx.rewrite.clone()) {
+            Some(notebook) => notebook,
+            None => self.read_all()?,
+        };
+        if let Some(tx) = &self.tx {
+            for (kind, id, record) in &tx.staged {
+                overlay(&mut notebook, *kind, *id, record.clone());
+            }
+        }
+        Ok(notebook)
+    }
+
+    fn save_notebook(&mut self, notebook: &Notebook) -> io::Result<()> {
+        self.change(|tx| {
+            tx.rewrite = Some(notebook.clone());
+            tx.staged.clear();
+        })
+    }
+
+    /// Write just the record and its parent, whose list of children may have changed.
+    fn write_record(&mut self, notebook: &Notebook, kind: NodeKind, id: usize) -> io::Result<()> {
+        // A record that's been removed is only in the store now
+        let record = match Stored::from_notebook(notebook, kind, id) {
+            Some(record) => Some(record),
+            None => self.load_stored(kind, id)?,
+        };
+        let parent = record.as_ref().and_then(Stored::parent);
+        self.change(|tx| {
+            BinaryStore::stage(tx, notebook, kind, id);
+            if let Some((parent_kind, parent_id)) = parent {
+                BinaryStore::stage(tx, notebook, parent_kind, parent_id);
+            }
+        })
+    }
+
+    fn begin(&mut self) -> io::Result<()> {
+        if self.tx.is_some() {
+            return Err(io::Error::other("A change is already in progress"));
+        }
+        let lock = LockFile::acquire(&self.meta_path)?;
+        let last_id = read_counter(&read_metadata(&self.meta_path)?)?;
+        self.tx = Some(BinaryTx {
+            last_id,
+            allocated: false,
+            rewrite: None,
+            staged: Vec::new(),
+            _lock: lock,
+        });
+        Ok(())
+    }
+
+    fn allocate_id(&mut self) -> io::Result<usize> {
+        let tx = self.tx.as_mut().ok_or_else(|| io::Error::other("No change in progress"))?;
+        tx.last_id += 1;
+        tx.allocated = true;
+        Ok(tx.last_id)
+    }
+
+    /// Single records are journaled along with the counter. A whole notebook
+    /// is saved after the counter, so a crash in between only skips some IDs.
+    fn commit(&mut self) -> io::Result<()> {
+        let tx = self.tx.take().ok_or_else(|| io::Error::other("No change in progress"))?;
+        let last_id = tx.allocated.then_some(tx.last_id);
+        if tx.rewrite.is_none() && !tx.staged.is_empty() {
+            return self.write_staged(tx.staged, last_id);
+        }
+
+        if let Some(last_id) = last_id {
+            let mut meta = read_metadata(&self.meta_path)?;
+            set_meta_value(&mut meta, "next.id", Some(last_id.to_string()));
+            write_metadata(&self.meta_path, &meta)?;
+        }
+        match tx.rewrite {
+            Some(mut notebook) => {
+                for (kind, id, record) in tx.staged {
+                    overlay(&mut notebook, kind, id, record);
+                }
+                self.write_all(&notebook)
+            }
+            None => Ok(()),
+        }
+    }
+
+    fn abort(&mut self) {
+        self.tx = None; // Dropping the transaction releases the lock
+    }
+
+    fn read_state(&mut self) -> io::Result<FunkState> {
+        read_state(&self.meta_path)
+    }
+
+    fn write_state(&mut self, state: &FunkState) -> io::Result<()> {
+        write_state(&self.meta_path, state)
+    }
+
+    fn find_by_title(&mut self, kind: NodeKind, title: &str) -> io::Result<Vec<usize>> {
+        // Anything staged isn't in the index yet
+        if self.tx.as_ref().is_some_and(|tx| tx.rewrite.is_some() || !tx.staged.is_empty()) {
+            let notebook = self.load_notebook()?;
+            return Ok(BinaryStore::titled(&notebook, kind, title));
+        }
+        self.find_title(kind, title)
+    }
+
+    // ===== Single records, straight from the index =====
+    fn load_project(&mut self, id: usize) -> io::Result<Option<Funknote>> {
+        Ok(match self.load_stored(NodeKind::Project, id)? {
+            Some(Stored::Note(note)) => Some(note),
+            _ => None,
+        })
+    }
+
+    fn load_object(&mut self, id: usize) -> io::Result<Option<Object>> {
+        Ok(match self.load_stored(NodeKind::Object, id)? {
+            Some(Stored::Object(object)) => Some(object),
+            _ => None,
+        })
+    }
+
+    fn load_item(&mut self, id: usize) -> io::Result<Option<Item>> {
+        Ok(match self.load_stored(NodeKind::Item, id)? {
+            Some(Stored::Item(item)) => Some(item),
+            _ => None,
+        })
+    }
+
+    fn load_milestone(&mut self, id: usize) -> io::Result<Option<Milestone>> {
+        Ok(match self.load_stored(NodeKind::Milestone, id)? {
+            Some(Stored::Milestone(milestone)) => Some(milestone),
+            _ => None,
+        })
+    }
+
+    fn update_project(&mut self, note: &Funknote) -> io::Result<()> {
+        self.update(NodeKind::Project, note.id, Stored::Note(note.clone()))
+    }
+
+    fn update_object(&mut self, object: &Object) -> io::Result<()> {
+        self.update(NodeKind::Object, object.id, Stored::Object(object.clone()))
+    }
+
+    fn update_item(&mut self, item: &Item) -> io::Result<()> {
+        self.update(NodeKind::Item, item.id, Stored::Item(item.clone()))
+    }
+
+    fn update_milestone(&mut self, milestone: &Milestone) -> io::Result<()> {
+        self.update(NodeKind::Milestone, milestone.id, Stored::Milestone(milestone.clone()))
+    }
+}
+
+impl BinaryStore {
+    // Replace a record without reading anything but that record
+    fn update(&mut self, kind: NodeKind, id: usize, record: Stored) -> io::Result<()> {
+        match self.load_stored(kind, id)? {
+            None => return Err(io::Error::new(io::ErrorKind::NotFound, format!("{} {} doesn't exist", kind.name(), id))),
+            Some(existing) if existing.parent() != record.parent() => return Err(parent_changed(kind, id)),
+            Some(_) => {}
+        }
+        self.change(|tx| tx.staged.push((kind, id, Some(record))))
+    }
+
+    fn titled(notebook: &Notebook, kind: NodeKind, title: &str) -> Vec<usize> {
+        let mut ids: Vec<usize> = all_of_kind(notebook, kind)
+            .iter()
+            .filter(|r| r.title() == Some(title))
+            .map(|r| r.id())
+            .collect();
+        ids.sort_unstable();
+        ids
+    }
+}
+
+// ====== RECORDS =====
+// Anything that gets a slot of its own
+#[derive(Debug, Clone, PartialEq)]
+enum Stored {
+    Note(Funknote),
+    Object(Object),
+    Item(Item),
+    Milestone(Milestone),
+    Trash(TrashEntry),   // Not in the index; only read with everything else
+}
+
+const TRASH_CODE: u8 = 5;
+
+fn kind_code(kind: NodeKind) -> u8 {
+    match kind {
+        NodeKind::Project => 1,
+        NodeKind::Object => 2,
+        NodeKind::Item => 3,
+        NodeKind::Milestone => 4,
+    }
+}
+
+fn kind_from_code(code: u8) -> io::Result<NodeKind> {
+    match code {
+        1 => Ok(NodeKind::Project),
+        2 => Ok(NodeKind::Object),
+        3 => Ok(NodeKind::Item),
+        4 => Ok(NodeKind::Milestone),
+        _ => Err(invalid(format!("Unknown record kind {} in {}", code, DATA_FILE))),
+    }
+}
+
+fn all_of_kind(notebook: &Notebook, kind: NodeKind) -> Vec<Stored> {
+    match kind {
+        NodeKind::Project => notebook.notes.iter().cloned().map(Stored::Note).collect(),
+        NodeKind::Object => notebook.objects.iter().cloned().map(Stored::Object).collect(),
+        NodeKind::Item => notebook.items.iter().cloned().map(Stored::Item).collect(),
+        NodeKind::Milestone => notebook.milestones.iter().cloned().map(Stored::Milestone).collect(),
+    }
+}
+
+impl Stored {
+    fn from_notebook(notebook: &Notebook, kind: NodeKind, id: usize) -> Option<Stored> {
+        all_of_kind(notebook, kind).into_iter().find(|r| r.id() == id)
+    }
+
+    fn code(&self) -> u8 {
+        match self {
+            Stored::Note(_) => kind_code(NodeKind::Project),
+            Stored::Object(_) => kind_code(NodeKind::Object),
+            Stored::Item(_) => kind_code(NodeKind::Item),
+            Stored::Milestone(_) => kind_code(NodeKind::Milestone),
+            Stored::Trash(_) => TRASH_CODE,
+        }
+    }
+
+    fn id(&self) -> usize {
+        match self {
+            Stored::Note(note) => note.id,
+            Stored::Object(object) => object.id,
+            Stored::Item(item) => item.id,
+            Stored::Milestone(milestone) => milestone.id,
+            Stored::Trash(entry) => entry.id,
+        }
+    }
+
+    fn parent(&self) -> Option<(NodeKind, usize)> {
+        match self {
+            Stored::Object(object) => Some((NodeKind::Project, object.project_id)),
+            Stored::Item(item) => Some((NodeKind::Object, item.object_id)),
+            Stored::Milestone(milestone) => Some((NodeKind::Project, milestone.project_id)),
+            Stored::Note(_) | Stored::Trash(_) => None,
+        }
+    }
+
+    fn title(&self) -> Option<&str> {
+        match self {
+            Stored::Note(note) => Some(&note.title),
+            Stored::Object(object) => Some(&object.title),
+            Stored::Item(item) => Some(&item.text),
+            Stored::Milestone(milestone) => Some(&milestone.title),
+            Stored::Trash(_) => None,
+        }
+    }
+
+    // The index entry for the record in the slot at offset. None for the trash.
+    fn entry(&self, offset: u64) -> Option<Entry> {
+        self.title().map(|title| Entry {
+            value: self.id() as u64,
+            kind: self.code(),
+            key: offset,
+            title: Some(title_hash(title)),
+        })
+    }
+
+    fn put_in(self, notebook: &mut Notebook) {
+        match self {
+            Stored::Note(note) => notebook.notes.push(note),
+            Stored::Object(object) => notebook.objects.push(object),
+            Stored::Item(item) => notebook.items.push(item),
+            Stored::Milestone(milestone) => notebook.milestones.push(milestone),
+            Stored::Trash(entry) => notebook.trash.push(entry),
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut e = Encoder(Vec::new());
+        match self {
+            Stored::Note(note) => {
+                e.id(note.id);
+                e.text(&note.title);
+                e.text(&note.description);
+                e.u64(note.created_on);
+                e.flag(note.active);
+                e.ids(&note.objects);
+                e.ids(&note.milestones);
+            }
+            Stored::Object(object) => {
+                e.id(object.id);
+                e.id(object.project_id);
+                e.text(&object.title);
+                e.text(&object.description);
+                e.u64(object.created_on);
+                e.flag(object.active);
+                e.ids(&object.items);
+            }
+            Stored::Item(item) => {
+                e.id(item.id);
+                e.id(item.object_id);
+                e.text(&item.text);
+                e.u64(item.created_on);
+                e.flag(item.completed);
+                e.date(item.completed_on);
+            }
+            Stored::Milestone(milestone) => {
+                e.id(milestone.id);
+                e.id(milestone.project_id);
+                e.text(&milestone.title);
+                e.text(&milestone.description);
+                e.u64(milestone.target_date);
+                e.flag(milestone.completed);
+                e.date(milestone.completed_on);
+                let (kind, id) = match milestone.target {
+                    MilestoneTarget::Project(id) => (NodeKind::Project, id),
+                    MilestoneTarget::Object(id) => (NodeKind::Object, id),
+                    MilestoneTarget::Item(id) => (NodeKind::Item, id),
+                };
+                e.0.push(kind_code(kind));
+                e.id(id);
+            }
+            Stored::Trash(entry) => {
+                // The entry, then each record deleted with it
+                e.0.push(kind_code(entry.kind));
+                e.id(entry.id);
+                e.u64(entry.deleted_on);
+                let contents = &entry.contents;
+                let records: Vec<Stored> = [NodeKind::Project, NodeKind::Object, NodeKind::Item, NodeKind::Milestone]
+                    .into_iter()
+                    .flat_map(|kind| all_of_kind(contents, kind))
+                    .collect();
+                e.u32(records.len() as u32);
+                for record in records {
+                    let payload = record.encode();
+                    e.0.push(record.code());
+                    e.u32(payload.len() as u32);
+                    e.0.extend(payload);
+                }
+            }
+        }
+        e.0
+    }
+
+    fn decode(code: u8, bytes: &[u8]) -> io::Result<Stored> {
+        let mut d = Decoder { bytes, pos: 0 };
+        let record = match code {
+            TRASH_CODE => {
+                let kind = kind_from_code(d.u8()?)?;
+                let id = d.id()?;
+                let deleted_on = d.u64()?;
+                let mut contents = Notebook::default();
+                for _ in 0..d.u32()? {
+                    let code = d.u8()?;
+                    let len = d.u32()? as usize;
+                    match Stored::decode(code, d.take(len)?)? {
+                        Stored::Trash(_) => return Err(invalid("A trash entry inside a trash entry".to_string())),
+                        record => record.put_in(&mut contents),
+                    }
+                }
+                Stored::Trash(TrashEntry { kind, id, deleted_on, contents })
+            }
+            code => match kind_from_code(code)? {
+                NodeKind::Project => Stored::Note(Funknote {
+                    id: d.id()?,
+                    title: d.text()?,
+                    description: d.text()?,
+                    created_on: d.u64()?,
+                    active: d.flag()?,
+                    objects: d.ids()?,
+                    milestones: d.ids()?,
+                }),
+                NodeKind::Object => Stored::Object(Object {
+                    id: d.id()?,
+                    project_id: d.id()?,
+                    title: d.text()?,
+                    description: d.text()?,
+                    created_on: d.u64()?,
+                    active: d.flag()?,
+                    items: d.ids()?,
+                }),
+                NodeKind::Item => Stored::Item(Item {
+                    id: d.id()?,
+                    object_id: d.id()?,
+                    text: d.text()?,
+                    created_on: d.u64()?,
+                    completed: d.flag()?,
+                    completed_on: d.date()?,
+                }),
+                NodeKind::Milestone => Stored::Milestone(Milestone {
+                    id: d.id()?,
+                    project_id: d.id()?,
+                    title: d.text()?,
+                    description: d.text()?,
+                    target_date: d.u64()?,
+                    completed: d.flag()?,
+                    completed_on: d.date()?,
+                    target: match kind_from_code(d.u8()?)? {
+                        NodeKind::Project => MilestoneTarget::Project(d.id()?),
+                        NodeKind::Object => MilestoneTarget::Object(d.id()?),
+                        NodeKind::Item => MilestoneTarget::Item(d.id()?),
+                        NodeKind::Milestone => return Err(invalid("A milestone can't track a milestone".to_string())),
+                    },
+                }),
+            },
+        };
+        if d.pos != bytes.len() {
+            return Err(invalid("A record is longer than its fields".to_string()));
+        }
+        Ok(record)
+    }
+}
+
+// Apply a single-record change to a notebook
+fn overlay(notebook: &mut Notebook, kind: NodeKind, id: usize, record: Option<Stored>) {
+    fn replace<T>(records: &mut Vec<T>, is_it: impl Fn(&T) -> bool, new: Option<T>) {
+        match (records.iter().position(is_it), new) {
+            (Some(i), Some(new)) => records[i] = new,
+            (Some(i), None) => {
+                records.remove(i);
+            }
+            (None, Some(new)) => records.push(new),
+            (None, None) => {}
+        }
+    }
+
+    match (kind, record) {
+        (NodeKind::Project, Some(Stored::Note(note))) => replace(&mut notebook.notes, |n| n.id == id, Some(note)),
+        (NodeKind::Project, _) => replace(&mut notebook.notes, |n| n.id == id, None),
+        (NodeKind::Object, Some(Stored::Object(object))) => replace(&mut notebook.objects, |o| o.id == id, Some(object)),
+        (NodeKind::Object, _) => replace(&mut notebook.objects, |o| o.id == id, None),
+        (NodeKind::Item, Some(Stored::Item(item))) => replace(&mut notebook.items, |i| i.id == id, Some(item)),
+        (NodeKind::Item, _) => replace(&mut notebook.items, |i| i.id == id, None),
+        (NodeKind::Milestone, Some(Stored::Milestone(m))) => replace(&mut notebook.milestones, |x| x.id == id, Some(m)),
+        (NodeKind::Milestone, _) => replace(&mut notebook.milestones, |x| x.id == id, None),
+    }
+}
+
+// ====== SLOTS =====
+struct Slot {
+    offset: u64,
+    capacity: u32,
+    live: bool,
+    record: Stored,
+}
+
+// Some room to grow, so small edits stay in place
+fn room_for(len: usize) -> u32 {
+    (len + len / 4 + 16) as u32
+}
+
+fn slot_bytes(capacity: u32, code: u8, live: bool, payload: &[u8]) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(SLOT_HEADER + capacity as usize);
+    bytes.extend_from_slice(&capacity.to_le_bytes());
+    bytes.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(&checksum(payload).to_le_bytes());
+    bytes.push(code);
+    bytes.push(u8::from(live));
+    bytes.extend_from_slice(payload);
+    bytes.resize(SLOT_HEADER + capacity as usize, 0);
+    bytes
+}
+
+fn parse_slot(bytes: &[u8], offset: u64) -> io::Result<Slot> {
+    let damaged = || damaged(offset);
+    let header = bytes.get(..SLOT_HEADER).ok_or_else(damaged)?;
+    let capacity = u32_at(header, 0);
+    let len = u32_at(header, 4) as usize;
+    let payload = bytes.get(SLOT_HEADER..SLOT_HEADER + len).ok_or_else(damaged)?;
+    if len > capacity as usize || checksum(payload) != u32_at(header, 8) {
+        return Err(damaged());
+    }
+
+    Ok(Slot {
+        offset,
+        capacity,
+        live: header[13] == 1,
+        record: Stored::decode(header[12], payload).map_err(|_| damaged())?,
+    })
+}
+
+fn read_slot(file: &mut File, offset: u64) -> io::Result<Slot> {
+    let mut header = [0u8; SLOT_HEADER];
+    file.seek(SeekFrom::Start(offset))?;
+    file.read_exact(&mut header)?;
+
+    // Check the length before trusting it with an allocation
+    let len = u32_at(&header, 4);
+    if len > u32_at(&header, 0) || offset + (SLOT_HEADER as u64) + len as u64 > file.metadata()?.len() {
+        return Err(damaged(offset));
+    }
+    let mut bytes = header.to_vec();
+    bytes.resize(SLOT_HEADER + len as usize, 0);
+    file.read_exact(&mut bytes[SLOT_HEADER..])?;
+    parse_slot(&bytes, offset)
+}
+
+fn damaged(offset: u64) -> io::Error {
+    invalid(format!("The record at byte {} of {} is damaged", offset, DATA_FILE))
+}
+
+// The write that marks a slot dead: its live byte, cleared
+fn dead_flag(slot: &Slot) -> (u64, Vec<u8>) {
+    (slot.offset + SLOT_HEADER as u64 - 1, vec![0])
+}
+
+// The last version staged of each record, which is the one that counts
+fn latest(staged: Vec<Staged>) -> Vec<Staged> {
+    let mut latest: Vec<Staged> = Vec::new();
+    for (kind, id, record) in staged.into_iter().rev() {
+        if !latest.iter().any(|(k, i, _)| *k == kind && *i == id) {
+            latest.push((kind, id, record));
+        }
+    }
+    latest.reverse();
+    latest
+}
+
+// FNV-1a, to notice a record that was only half written
+fn checksum(bytes: &[u8]) -> u32 {
+    bytes.iter().fold(0x811c9dc5u32, |hash, &b| (hash ^ b as u32).wrapping_mul(0x01000193))
+}
+
+// ====== INDEX =====
+// One entry in the index file. By ID: value = ID, key = slot offset.
+// By title: value = title hash, key = ID.
+#[derive(Debug, Clone, Copy)]
+struct Entry {
+    value: u64,
+    kind: u8,
+    key: u64,
+    title: Option<u64>,   // Title hash of an ID entry, before it's written
+}
+
+impl Entry {
+    fn from_bytes(bytes: &[u8]) -> Entry {
+        Entry { value: u64_at(bytes, 0), kind: bytes[8], key: u64_at(bytes, 9), title: None }
+    }
+
+    fn push_bytes(&self, bytes: &mut Vec<u8>) {
+        bytes.extend_from_slice(&self.value.to_le_bytes());
+        bytes.push(self.kind);
+        bytes.extend_from_slice(&self.key.to_le_bytes());
+    }
+}
+
+// How long an index with these counts is, if that fits in a u64
+fn index_size(id_count: u64, title_count: u64) -> Option<u64> {
+    id_count.checked_add(title_count)?.checked_mul(ENTRY_SIZE)?.checked_add(INDEX_HEADER)
+}
+
+fn index_counts(index: &mut File) -> io::Result<(u64, u64)> {
+    let mut header = [0u8; INDEX_HEADER as usize];
+    index.seek(SeekFrom::Start(0))?;
+    index.read_exact(&mut header)?;
+    Ok((u64_at(&header, 16), u64_at(&header, 24)))
+}
+
+fn read_entry(index: &mut File, i: u64) -> io::Result<Entry> {
+    let mut bytes = [0u8; ENTRY_SIZE as usize];
+    index.seek(SeekFrom::Start(INDEX_HEADER + i * ENTRY_SIZE))?;
+    index.read_exact(&mut bytes)?;
+    Ok(Entry::from_bytes(&bytes))
+}
+
+// Binary search of entries first..last for one that compares Equal
+fn search(index: &mut File, first: u64, last: u64, cmp: impl Fn(&Entry) -> std::cmp::Ordering) -> io::Result<Option<Entry>> {
+    let (mut first, mut last) = (first, last);
+    while first < last {
+        let mid = first + (last - first) / 2;
+        let entry = read_entry(index, mid)?;
+        match cmp(&entry) {
+            std::cmp::Ordering::Less => first = mid + 1,
+            std::cmp::Ordering::Greater => last = mid,
+            std::cmp::Ordering::Equal => return Ok(Some(entry)),
+        }
+    }
+    Ok(None)
+}
+
+// FNV-1a again, 64 bits this time, of the exact title
+fn title_hash(title: &str) -> u64 {
+    title.bytes().fold(0xcbf29ce484222325u64, |hash, b| (hash ^ b as u64).wrapping_mul(0x100000001b3))
+}
+
+// ====== ENCODING =====
+// Numbers are little-endian, text is a u32 length then UTF-8
+struct Encoder(Vec<u8>);
+
+impl Encoder {
+    fn u32(&mut self, n: u32) {
+        self.0.extend_from_slice(&n.to_le_bytes());
+    }
+
+    fn u64(&mut self, n: u64) {
+        self.0.extend_from_slice(&n.to_le_bytes());
+    }
+
+    fn id(&mut self, id: usize) {
+        self.u64(id as u64);
+    }
+
+    fn flag(&mut self, flag: bool) {
+        self.0.push(u8::from(flag));
+    }
+
+    fn text(&mut self, text: &str) {
+        self.u32(text.len() as u32);
+        self.0.extend_from_slice(text.as_bytes());
+    }
+
+    fn ids(&mut self, ids: &[usize]) {
+        self.u32(ids.len() as u32);
+        for id in ids {
+            self.id(*id);
+        }
+    }
+
+    fn date(&mut self, date: Option<u64>) {
+        self.flag(date.is_some());
+        self.u64(date.unwrap_or(0));
+    }
+}
+
+struct Decoder<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> Decoder<'a> {
+    fn take(&mut self, len: usize) -> io::Result<&'a [u8]> {
+        let bytes = self.bytes.get(self.pos..self.pos + len).ok_or_else(|| invalid("A record is cut short".to_string()))?;
+        self.pos += len;
+        Ok(bytes)
+    }
+
+    fn u8(&mut self) -> io::Result<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn u32(&mut self) -> io::Result<u32> {
+        Ok(u32_at(self.take(4)?, 0))
+    }
+
+    fn u64(&mut self) -> io::Result<u64> {
+        Ok(u64_at(self.take(8)?, 0))
+    }
+
+    fn id(&mut self) -> io::Result<usize> {
+        Ok(self.u64()? as usize)
+    }
+
+    fn flag(&mut self) -> io::Result<bool> {
+        Ok(self.u8()? != 0)
+    }
+
+    fn text(&mut self) -> io::Result<String> {
+        let len = self.u32()? as usize;
+        String::from_utf8(self.take(len)?.to_vec()).map_err(|_| invalid("Text that isn't UTF-8".to_string()))
+    }
+
+    fn ids(&mut self) -> io::Result<Vec<usize>> {
+        (0..self.u32()?).map(|_| self.id()).collect()
+    }
+
+    fn date(&mut self) -> io::Result<Option<u64>> {
+        let set = self.flag()?;
+        let date = self.u64()?;
+        Ok(set.then_some(date))
+    }
+}
+
+fn u32_at(bytes: &[u8], at: usize) -> u32 {
+    u32::from_le_bytes(bytes[at..at + 4].try_into().expect("four bytes"))
+}
+
+fn u64_at(bytes: &[u8], at: usize) -> u64 {
+    u64::from_le_bytes(bytes[at..at + 8].try_into().expect("eight bytes"))
+}
+
+fn invalid(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
+
+
+// ================== TESTS ===================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::storage::{convert_store, open_backend, Backend};
+    use crate::store::fixtures::{sample_notebook, sample_trash, temp_dir};
+
+    // The sample with something in the trash, which the store keeps too
+    fn stored_notebook() -> Notebook {
+        let mut notebook = sample_notebook();
+        notebook.trash.push(sample_trash());
+        notebook
+    }
+
+    fn saved_store(name: &str) -> (PathBuf, BinaryStore) {
+        let dir = temp_dir(name);
+        let mut store = BinaryStore::open(&dir).unwrap();
+        store.save_notebook(&stored_notebook()).unwrap();
+
+        let meta_path = dir.join(METADATA_FILE);
+        let mut meta = read_metadata(&meta_path).unwrap();
+        set_meta_value(&mut meta, "next.id", Some("7".to_string()));
+        write_metadata(&meta_path, &meta).unwrap();
+        (dir, store)
+    }
+
+    #[test]
+    fn test_binary_round_trip() {
+        let (dir, _) = saved_store("round_trip");
+        let mut reopened = BinaryStore::open(&dir).unwrap();
+        assert_eq!(reopened.load_notebook().unwrap(), stored_notebook());
+    }
+
+    #[test]
+    fn test_lookups_use_the_index() {
+        let (_, mut store) = saved_store("lookups");
+
+        assert_eq!(store.load_object(2).unwrap().unwrap().title, "Beds");
+        assert_eq!(store.load_item(3).unwrap().unwrap().completed_on, Some(1764633700));
+        assert_eq!(store.load_milestone(4).unwrap().unwrap().target, MilestoneTarget::Item(3));
+        assert!(store.load_project(2).unwrap().is_none());  // 2 is an object
+        assert!(store.load_item(6).unwrap().is_none());     // 6 is in the trash
+        assert!(store.load_item(99).unwrap().is_none());
+
+        assert_eq!(store.find_by_title(NodeKind::Project, "Garden").unwrap(), vec![1]);
+        assert_eq!(store.find_by_title(NodeKind::Item, "Buy soil").unwrap(), vec![3]);
+        assert!(store.find_by_title(NodeKind::Object, "Garden").unwrap().is_empty());
+        assert!(store.find_by_title(NodeKind::Item, "Pull weeds").unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_small_update_stays_in_place() {
+        let (dir, mut store) = saved_store("in_place");
+        let size = fs::metadata(dir.join(DATA_FILE)).unwrap().len();
+
+        let mut item = store.load_item(3).unwrap().unwrap();
+        item.text = "Buy more soil".to_string();
+        store.update_item(&item).unwrap();
+
+        assert_eq!(fs::metadata(dir.join(DATA_FILE)).unwrap().len(), size);
+        assert_eq!(store.load_item(3).unwrap().unwrap().text, "Buy more soil");
+        assert_eq!(store.find_by_title(NodeKind::Item, "Buy more soil").unwrap(), vec![3]);
+        assert!(store.find_by_title(NodeKind::Item, "Buy soil").unwrap().is_empty());
+
+        // Same slot, same title: the index is patched, not written out again.
+        // A rewrite would replace the file this handle has open.
+        let mut old_index = File::open(dir.join(INDEX_FILE)).unwrap();
+        let entries = fs::read(dir.join(INDEX_FILE)).unwrap()[INDEX_HEADER as usize..].to_vec();
+        item.completed = false;
+        store.update_item(&item).unwrap();
+        let mut patched = Vec::new();
+        old_index.read_to_end(&mut patched).unwrap();
+        assert_eq!(u64_at(&patched, 8), store.generation().unwrap());
+        assert_eq!(patched[INDEX_HEADER as usize..], entries[..]);
+        assert!(!store.load_item(3).unwrap().unwrap().completed);
+
+        // Moving it to another object isn't an update
+        let generation = store.generation().unwrap();
+        let moved = Item { object_id: 9, ..item };
+        assert_eq!(store.update_item(&moved).unwrap_err().kind(), io::ErrorKind::InvalidInput);
+        assert_eq!(store.generation().unwrap(), generation);
+        assert_eq!(store.load_item(3).unwrap().unwrap().object_id, 2);
+    }
+
+    #[test]
+    fn test_grown_record_moves_to_the_end() {
+        let (dir, mut store) = saved_store("grown");
+        let size = fs::metadata(dir.join(DATA_FILE)).unwrap().len();
+
+        let mut object = store.load_object(2).unwrap().unwrap();
+        object.description = "Raised beds along the south fence, cedar, lined".repeat(4);
+        store.update_object(&object).unwrap();
+
+        assert!(fs::metadata(dir.join(DATA_FILE)).unwrap().len() > size);
+        let mut expected = stored_notebook();
+        expected.objects[0] = object;
+        assert_eq!(BinaryStore::open(&dir).unwrap().load_notebook().unwrap(), expected);
+
+        // The dead copy is only skipped, and a full save drops it
+        store.save_notebook(&expected).unwrap();
+        assert_eq!(store.load_notebook().unwrap(), expected);
+    }
+
+    #[test]
+    fn test_write_record_adds_and_removes() {
+        let (_, mut store) = saved_store("write_record");
+        let mut notebook = store.load_notebook().unwrap();
+
+        store.begin().unwrap();
+        let id = store.allocate_id().unwrap();
+        assert_eq!(id, 8);
+        notebook.items.push(Item {
+            id,
+            object_id: 2,
+            text: "Water".to_string(),
+            created_on: 1764633900,
+            completed: false,
+            completed_on: None,
+        });
+        notebook.objects[0].items.push(id);
+        store.write_record(&notebook, NodeKind::Item, id).unwrap();
+        assert_eq!(store.load_notebook().unwrap(), notebook); // Seen before commit
+        store.commit().unwrap();
+        assert_eq!(store.load_notebook().unwrap(), notebook);
+        assert_eq!(store.find_by_title(NodeKind::Item, "Water").unwrap(), vec![id]);
+
+        notebook.items.retain(|i| i.id != id);
+        notebook.objects[0].items.retain(|i| *i != id);
+        store.write_record(&notebook, NodeKind::Item, id).unwrap();
+        assert_eq!(store.load_notebook().unwrap(), notebook);
+        assert!(store.load_item(id).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_interrupted_commit_is_finished() {
+        let (dir, mut store) = saved_store("interrupted");
+        let mut notebook = store.load_notebook().unwrap();
+
+        // A new item, which grows its object out of its slot, and a renamed project
+        store.begin().unwrap();
+        let id = store.allocate_id().unwrap();
+        notebook.items.push(Item { id, text: "Water".to_string(), ..notebook.items[0].clone() });
+        notebook.objects[0].items.push(id);
+        notebook.objects[0].description = "Raised beds along the south fence".repeat(4);
+        store.write_record(&notebook, NodeKind::Item, id).unwrap();
+        notebook.notes[0].title = "Allotment".to_string();
+        store.update_project(&notebook.notes[0]).unwrap();
+
+        // Died with the journal saved and the data file part way through it
+        let tx = store.tx.take().unwrap();
+        let (journal, _, _) = store.plan_writes(tx.staged, Some(tx.last_id)).unwrap();
+        assert!(journal.writes.len() > 3, "{:?}", journal.writes.len());
+        let rendered = crate::storage::render_journal(&journal);
+        fs::write(dir.join(JOURNAL_FILE), &rendered).unwrap();
+        let torn = (0..journal.writes.len()).max_by_key(|i| journal.writes[*i].1.len()).unwrap();
+        let mut data = fs::OpenOptions::new().write(true).open(dir.join(DATA_FILE)).unwrap();
+        for (offset, bytes) in &journal.writes[..torn] {
+            data.seek(SeekFrom::Start(*offset)).unwrap();
+            std::io::Write::write_all(&mut data, bytes).unwrap();
+        }
+        let (offset, bytes) = &journal.writes[torn];
+        data.seek(SeekFrom::Start(*offset)).unwrap();
+        std::io::Write::write_all(&mut data, &bytes[..bytes.len() / 2]).unwrap();
+        drop((data, tx._lock));
+        let unrecovered = BinaryStore {
+            data_path: dir.join(DATA_FILE),
+            index_path: dir.join(INDEX_FILE),
+            meta_path: dir.join(METADATA_FILE),
+            journal_path: dir.join(JOURNAL_FILE),
+            tx: None,
+        };
+        assert_eq!(unrecovered.read_all().unwrap_err().kind(), io::ErrorKind::InvalidData);
+
+        let mut reopened = BinaryStore::open(&dir).unwrap();
+        assert!(!dir.join(JOURNAL_FILE).exists());
+        assert_eq!(reopened.load_notebook().unwrap(), notebook);
+        assert_eq!(reopened.find_by_title(NodeKind::Project, "Allotment").unwrap(), vec![1]);
+        reopened.begin().unwrap();
+        assert_eq!(reopened.allocate_id().unwrap(), id + 1);
+        reopened.abort();
+
+        // A journal cut short means the change never started
+        let before = reopened.load_notebook().unwrap();
+        fs::write(dir.join(JOURNAL_FILE), &rendered[..rendered.len() - 5]).unwrap();
+        assert_eq!(BinaryStore::open(&dir).unwrap().load_notebook().unwrap(), before);
+        assert!(!dir.join(JOURNAL_FILE).exists());
+    }
+
+    #[test]
+    fn test_stale_index_is_rebuilt() {
+        let (dir, mut store) = saved_store("stale_index");
+        let old_index = fs::read(dir.join(INDEX_FILE)).unwrap();
+
+        let mut note = store.load_project(1).unwrap().unwrap();
+        note.title = "Allotment".to_string();
+        store.update_project(&note).unwrap();
+
+        // As if a crash came between the data file and the index
+        fs::write(dir.join(INDEX_FILE), old_index).unwrap();
+        let mut reopened = BinaryStore::open(&dir).unwrap();
+        assert_eq!(reopened.find_by_title(NodeKind::Project, "Allotment").unwrap(), vec![1]);
+
+        fs::remove_file(dir.join(INDEX_FILE)).unwrap();
+        let mut reopened = BinaryStore::open(&dir).unwrap();
+        assert_eq!(reopened.load_project(1).unwrap().unwrap().title, "Allotment");
+
+        // Cut off partway through its entries, with the generation still current
+        let index = fs::read(dir.join(INDEX_FILE)).unwrap();
+        fs::write(dir.join(INDEX_FILE), &index[..index.len() - 5]).unwrap();
+        let mut reopened = BinaryStore::open(&dir).unwrap();
+        assert_eq!(fs::read(dir.join(INDEX_FILE)).unwrap(), index);
+        assert_eq!(reopened.find_by_title(NodeKind::Item, "Buy soil").unwrap(), vec![3]);
+    }
+
+    #[test]
+    fn test_damaged_record_is_reported() {
+        let (dir, _) = saved_store("damaged");
+        let mut bytes = fs::read(dir.join(DATA_FILE)).unwrap();
+        let at = DATA_HEADER as usize + SLOT_HEADER + 2;
+        bytes[at] ^= 0xff;
+        fs::write(dir.join(DATA_FILE), bytes).unwrap();
+
+        let e = BinaryStore::open(&dir).unwrap().load_notebook().unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(e.to_string().contains("byte 16"), "{}", e);
+
+        // A length past the end of the file is reported before anything is read
+        let (dir, store) = saved_store("damaged_length");
+        let offset = store.find_id(3).unwrap().unwrap().key;
+        let mut bytes = fs::read(dir.join(DATA_FILE)).unwrap();
+        let at = offset as usize;
+        bytes[at..at + 8].copy_from_slice(&[0xff; 8]); // Capacity and length
+        fs::write(dir.join(DATA_FILE), bytes).unwrap();
+
+        let e = BinaryStore::open(&dir).unwrap().load_item(3).unwrap_err();
+        assert_eq!(e.kind(), io::ErrorKind::InvalidData);
+        assert!(e.to_string().contains(&format!("byte {}", offset)), "{}", e);
+    }
+
+    #[test]
+    fn test_convert_between_stores() {
+        let dir = temp_dir("convert");
+        let mut text = open_backend(&dir, Backend::Text).unwrap();
+        text.save_notebook(&stored_notebook()).unwrap();
+
+        convert_store(&dir, Backend::Text, Backend::Binary).unwrap();
+        assert_eq!(crate::storage::backend(&dir, None).unwrap(), Backend::Binary);
+        let mut binary = open_backend(&dir, Backend::Binary).unwrap();
+        assert_eq!(binary.load_notebook().unwrap(), stored_notebook());
+
+        let mut notebook = stored_notebook();
+        notebook.notes[0].title = "Allotment".to_string();
+        binary.save_notebook(&notebook).unwrap();
+
+        convert_store(&dir, Backend::Binary, Backend::Text).unwrap();
+        assert_eq!(open_backend(&dir, Backend::Text).unwrap().load_notebook().unwrap(), notebook);
+        assert_eq!(crate::storage::backend(&dir, Some("binary")).unwrap(), Backend::Binary);
+        assert!(convert_store(&dir, Backend::Text, Backend::Text).is_err());
+    }
+}
@@ -0,0 +1,1240 @@
+use std::collections::HashMap;
+
+use crate::timestamp::{string_to_timestamp, timestamp_to_string};
+use crate::types::*;
+
+// ====== JSON =====
+// The whole live tree as nested JSON, for other tools. The trash isn't
+// exported.
+//
+//   {
+//     "funknotes": 1,
+//     "projects": [
+//       { "id": 1, "title": "Garden", ..., "objects": [
+//           { "id": 2, ..., "items": [ { "id": 3, "text": "Buy soil", ... } ] } ],
+//         "milestones": [ { "id": 4, ..., "target": { "kind": "object", "id": 2 } } ] }
+//     ]
+//   }
+//
+// IDs are the ones in the store. On import they only tie records together
+// (mostly milestone targets) and every record is given a fresh ID.
+
+// Bumped if the layout above changes in a way older builds can't read
+const JSON_VERSION: u64 = 1;
+
+/// Render every live project, and everything under it, as JSON.
+pub fn notebook_to_json(notebook: &Notebook) -> String {
+    let projects = notebook.notes.iter().map(|note| project_to_json(note, notebook)).collect();
+    let root = Json::Object(vec![
+        ("funknotes".to_string(), Json::Number(JSON_VERSION.to_string())),
+        ("projects".to_string(), Json::Array(projects)),
+    ]);
+
+    let mut rendered = String::new();
+    render_json(&root, 0, &mut rendered);
+    rendered.push('\n');
+    rendered
+}
+
+/// Read a JSON export back into a notebook, keeping the IDs it was written with.
+pub fn notebook_from_json(contents: &str) -> Result<Notebook, String> {
+    let root = parse_json(contents)?;
+    let root = Fields::of(&root, "the file")?;
+
+    let version = root.number("funknotes")?;
+    if version > JSON_VERSION {
+        return Err(format!(
+            "The file is funknotes JSON version {}, but this funknotes only reads up to {}",
+            version, JSON_VERSION
+        ));
+    }
+
+    let mut notebook = Notebook::default();
+    for (i, project) in root.array("projects")?.iter().enumerate() {
+        read_project(&Fields::of(project, &format!("projects[{}]", i))?, &mut notebook)?;
+    }
+
+    // IDs are what tie records together, so they have to be unique
+    let mut ids: Vec<usize> = notebook.notes.iter().map(|n| n.id)
+        .chain(notebook.objects.iter().map(|o| o.id))
+        .chain(notebook.items.iter().map(|i| i.id))
+        .chain(notebook.milestones.iter().map(|m| m.id))
+        .collect();
+    ids.sort_unstable();
+    if let Some(pair) = ids.windows(2).find(|p| p[0] == p[1]) {
+        return Err(format!("ID {} is used by more than one record", pair[0]));
+    }
+    // and everything a milestone tracks has to be in the file too
+    for milestone in &notebook.milestones {
+        let found = match milestone.target {
+            MilestoneTarget::Project(id) => notebook.notes.iter().any(|n| n.id == id),
+            MilestoneTarget::Object(id) => notebook.objects.iter().any(|o| o.id == id),
+            MilestoneTarget::Item(id) => notebook.items.iter().any(|i| i.id == id),
+        };
+        if !found {
+            return Err(format!("Milestone '{}' tracks something that isn't in the file", milestone.title));
+        }
+    }
+    Ok(notebook)
+}
+
+/// Give every record in the notebook a new ID from new_id, keeping all the
+/// links between them. Used when importing, so nothing clashes with the store.
+/// A link to a record that isn't in the notebook is an error.
+pub fn renumber(notebook: &mut Notebook, mut new_id: impl FnMut() -> Result<usize, String>) -> Result<(), String> {
+    let mut mapping: HashMap<usize, usize> = HashMap::new();
+    let mut assign = |id: &mut usize| -> Result<(), String> {
+        let new = new_id()?;
+        mapping.insert(*id, new);
+        *id = new;
+        Ok(())
+    };
+
+    for note in &mut notebook.notes {
+        assign(&mut note.id)?;
+    }
+    for object in &mut notebook.objects {
+        assign(&mut object.id)?;
+    }
+    for item in &mut notebook.items {
+        assign(&mut item.id)?;
+    }
+    for milestone in &mut notebook.milestones {
+        assign(&mut milestone.id)?;
+    }
+
+    // IDs are unique across every kind, so one lookup does for all the links
+    let relink = |id: &mut usize| -> Result<(), String> {
+        *id = *mapping.get(id).ok_or_else(|| format!("ID {} links to a record that isn't being imported", id))?;
+        Ok(())
+    };
+    for note in &mut notebook.notes {
+        note.objects.iter_mut().chain(note.milestones.iter_mut()).try_for_each(relink)?;
+    }
+    for object in &mut notebook.objects {
+        relink(&mut object.project_id)?;
+        object.items.iter_mut().try_for_each(relink)?;
+    }
+    for item in &mut notebook.items {
+        relink(&mut item.object_id)?;
+    }
+    for milestone in &mut notebook.milestones {
+        relink(&mut milestone.project_id)?;
+        match &mut milestone.target {
+            MilestoneTarget::Project(id) | MilestoneTarget::Object(id) | MilestoneTarget::Item(id) => relink(id)?,
+        }
+    }
+    Ok(())
+}
+
+fn project_to_json(note: &Funknote, notebook: &Notebook) -> Json {
+    let objects = notebook
+        .objects
+        .iter()
+        .filter(|o| note.objects.contains(&o.id))
+        .map(|object| {
+            let items = notebook
+                .items
+                .iter()
+                .filter(|i| object.items.contains(&i.id))
+                .map(|item| Json::Object(vec![
+                    ("id".to_string(), number(item.id as u64)),
+                    ("text".to_string(), Json::String(item.text.clone())),
+                    ("created_on".to_string(), number(item.created_on)),
+                    ("completed".to_string(), Json::Bool(item.completed)),
+                    ("completed_on".to_string(), item.completed_on.map_or(Json::Null, number)),
+                ]))
+                .collect();
+            Json::Object(vec![
+                ("id".to_string(), number(object.id as u64)),
+                ("title".to_string(), Json::String(object.title.clone())),
+                ("description".to_string(), Json::String(object.description.clone())),
+                ("created_on".to_string(), number(object.created_on)),
+                ("active".to_string(), Json::Bool(object.active)),
+                ("items".to_string(), Json::Array(items)),
+            ])
+        })
+        .collect();
+
+    let milestones = notebook
+        .milestones
+        .iter()
+        .filter(|m| note.milestones.contains(&m.id))
+        .map(|milestone| {
+            let (kind, id) = match milestone.target {
+                MilestoneTarget::Project(id) => ("project", id),
+                MilestoneTarget::Object(id) => ("object", id),
+                MilestoneTarget::Item(id) => ("item", id),
+            };
+            Json::Object(vec![
+                ("id".to_string(), number(milestone.id as u64)),
+                ("title".to_string(), Json::String(milestone.title.clone())),
+                ("description".to_string(), Json::String(milestone.description.clone())),
+                ("target_date".to_string(), number(milestone.target_date)),
+                ("completed".to_string(), Json::Bool(milestone.completed)),
+                ("completed_on".to_string(), milestone.completed_on.map_or(Json::Null, number)),
+                ("target".to_string(), Json::Object(vec![
+                    ("kind".to_string(), Json::String(kind.to_string())),
+                    ("id".to_string(), number(id as u64)),
+                ])),
+            ])
+        })
+        .collect();
+
+    Json::Object(vec![
+        ("id".to_string(), number(note.id as u64)),
+        ("title".to_string(), Json::String(note.title.clone())),
+        ("description".to_string(), Json::String(note.description.clone())),
+        ("created_on".to_string(), number(note.created_on)),
+        ("active".to_string(), Json::Bool(note.active)),
+        ("objects".to_string(), Json::Array(objects)),
+        ("milestones".to_string(), Json::Array(milestones)),
+    ])
+}
+
+fn number(n: u64) -> Json {
+    Json::Number(n.to_string())
+}
+
+// Anything left out gets the same value new() would give it
+fn read_project(fields: &Fields, notebook: &mut Notebook) -> Result<(), String> {
+    let mut note = Funknote {
+        id: fields.id()?,
+        title: fields.string("title")?,
+        description: fields.string_or("description", "")?,
+        created_on: fields.number_or("created_on", 0)?,
+        active: fields.bool_or("active", true)?,
+        objects: Vec::new(),
+        milestones: Vec::new(),
+    };
+
+    for (i, object) in fields.array_or_empty("objects")?.iter().enumerate() {
+        let fields = Fields::of(object, &format!("{}.objects[{}]", fields.path, i))?;
+        let mut object = Object {
+            id: fields.id()?,
+            project_id: note.id,
+            title: fields.string("title")?,
+            description: fields.string_or("description", "")?,
+            created_on: fields.number_or("created_on", 0)?,
+            active: fields.bool_or("active", true)?,
+            items: Vec::new(),
+        };
+
+        for (j, item) in fields.array_or_empty("items")?.iter().enumerate() {
+            let fields = Fields::of(item, &format!("{}.items[{}]", fields.path, j))?;
+            let item = Item {
+                id: fields.id()?,
+                object_id: object.id,
+                text: fields.string("text")?,
+                created_on: fields.number_or("created_on", 0)?,
+                completed: fields.bool_or("completed", false)?,
+                completed_on: fields.optional_number("completed_on")?,
+            };
+            object.items.push(item.id);
+            notebook.items.push(item);
+        }
+        note.objects.push(object.id);
+        notebook.objects.push(object);
+    }
+
+    for (i, milestone) in fields.array_or_empty("milestones")?.iter().enumerate() {
+        let fields = Fields::of(milestone, &format!("{}.milestones[{}]", fields.path, i))?;
+        let target = match fields.get("target") {
+            None | Some(Json::Null) => MilestoneTarget::Project(note.id),
+            Some(target) => {
+                let target = Fields::of(target, &format!("{}.target", fields.path))?;
+                let id = target.id()?;
+                match target.string("kind")?.as_str() {
+                    "project" => MilestoneTarget::Project(id),
+                    "object" => MilestoneTarget::Object(id),
+                    "item" => MilestoneTarget::Item(id),
+                    other => return Err(format!(
+                        "{}: \"kind\" has to be project, object or item, not '{}'", target.path, other
+                    )),
+                }
+            }
+        };
+        let milestone = Milestone {
+            id: fields.id()?,
+            project_id: note.id,
+            title: fields.string("title")?,
+            description: fields.string_or("description", "")?,
+            target_date: fields.number_or("target_date", 0)?,
+            completed: fields.bool_or("completed", false)?,
+            completed_on: fields.optional_number("completed_on")?,
+            target,
+        };
+        note.milestones.push(milestone.id);
+        notebook.milestones.push(milestone);
+    }
+
+    notebook.notes.push(note);
+    Ok(())
+}
+
+// The fields of a JSON object, with where it is in the file for errors
+struct Fields<'a> {
+    fields: &'a [(String, Json)],
+    path: String,
+}
+
+impl<'a> Fields<'a> {
+    fn of(value: &'a Json, path: &str) -> Result<Fields<'a>, String> {
+        match value {
+            Json::Object(fields) => Ok(Fields { fields, path: path.to_string() }),
+            _ => Err(format!("{} has to be a JSON object", path)),
+        }
+    }
+
+    fn get(&self, key: &str) -> Option<&'a Json> {
+        self.fields.iter().find(|(k, _)| k == key).map(|(_, v)| v)
+    }
+
+    fn wrong_type(&self, key: &str, expected: &str) -> String {
+        format!("{}: \"{}\" has to be {}", self.path, key, expected)
+    }
+
+    fn string(&self, key: &str) -> Result<String, String> {
+        match self.get(key) {
+            Some(Json::String(s)) => Ok(s.clone()),
+            Some(_) => Err(self.wrong_type(key, "a string")),
+            None => Err(format!("{}: \"{}\" is missing", self.path, key)),
+        }
+    }
+
+    fn string_or(&self, key: &str, default: &str) -> Result<String, String> {
+        match self.get(key) {
+            None | Some(Json::Null) => Ok(default.to_string()),
+            Some(_) => self.string(key),
+        }
+    }
+
+    fn number(&self, key: &str) -> Result<u64, String> {
+        match self.get(key) {
+            Some(Json::Number(n)) => n.parse::<u64>().map_err(|_| self.wrong_type(key, "a whole number, 0 or more")),
+            Some(_) => Err(self.wrong_type(key, "a number")),
+            None => Err(format!("{}: \"{}\" is missing", self.path, key)),
+        }
+    }
+
+    fn number_or(&self, key: &str, default: u64) -> Result<u64, String> {
+        Ok(self.optional_number(key)?.unwrap_or(default))
+    }
+
+    fn optional_number(&self, key: &str) -> Result<Option<u64>, String> {
+        match self.get(key) {
+            None | Some(Json::Null) => Ok(None),
+            Some(_) => self.number(key).map(Some),
+        }
+    }
+
+    fn id(&self) -> Result<usize, String> {
+        Ok(self.number("id")? as usize)
+    }
+
+    fn bool_or(&self, key: &str, default: bool) -> Result<bool, String> {
+        match self.get(key) {
+            None | Some(Json::Null) => Ok(default),
+            Some(Json::Bool(b)) => Ok(*b),
+            Some(_) => Err(self.wrong_type(key, "true or false")),
+        }
+    }
+
+    fn array(&self, key: &str) -> Result<&'a [Json], String> {
+        match self.get(key) {
+            Some(Json::Array(values)) => Ok(values),
+            Some(_) => Err(self.wrong_type(key, "a list")),
+            None => Err(format!("{}: \"{}\" is missing", self.path, key)),
+        }
+    }
+
+    fn array_or_empty(&self, key: &str) -> Result<&'a [Json], String> {
+        match self.get(key) {
+            None | Some(Json::Null) => Ok(&[]),
+            Some(_) => self.array(key),
+        }
+    }
+}
+
+// ====== MARKDOWN =====
+// A project as a checklist document:
+//
+//   # Garden
+//
+//   ## Beds
+//
+//   - [x] Buy soil (done 2025-12-02)
+//   - [ ] Water daily
+//
+//   ## Milestones
+//
+//   | Milestone | Tracks | Target date | Completed |
+//   | --- | --- | --- | --- |
+//   | Soil in | Item: Buy soil | 2026-01-01 | |
+//
+// Descriptions go in a paragraph under their heading.
+
+/// Render a project, and everything under it, as markdown.
+pub fn project_to_markdown(note: &Funknote, notebook: &Notebook) -> String {
+    let mut rendered = format!("# {}\n", markdown_line(&note.title));
+    push_description(&mut rendered, &note.description);
+
+    for object in notebook.objects.iter().filter(|o| note.objects.contains(&o.id)) {
+        rendered.push_str(&format!("\n## {}\n", markdown_line(&object.title)));
+        push_description(&mut rendered, &object.description);
+
+        let items: Vec<&Item> = notebook.items.iter().filter(|i| object.items.contains(&i.id)).collect();
+        if !items.is_empty() {
+            rendered.push('\n');
+        }
+        for item in items {
+            let checkbox = if item.completed { "[x]" } else { "[ ]" };
+            rendered.push_str(&format!("- {} {}", checkbox, markdown_line(&item.text)));
+            if let Some(ts) = item.completed_on.filter(|_| item.completed) {
+                rendered.push_str(&format!(" (done {})", timestamp_to_string(ts)));
+            }
+            rendered.push('\n');
+        }
+    }
+
+    let milestones: Vec<&Milestone> = notebook.milestones.iter().filter(|m| note.milestones.contains(&m.id)).collect();
+    if !milestones.is_empty() {
+        rendered.push_str("\n## Milestones\n\n");
+        rendered.push_str("| Milestone | Tracks | Target date | Completed |\n");
+        rendered.push_str("| --- | --- | --- | --- |\n");
+    }
+    for milestone in milestones {
+        let tracks = match milestone.target {
+            MilestoneTarget::Project(_) => Some("Whole project".to_string()),
+            MilestoneTarget::Object(id) => notebook.objects.iter().find(|o| o.id == id)
+                .map(|o| format!("Object: {}", o.title)),
+            MilestoneTarget::Item(id) => notebook.items.iter().find(|i| i.id == id)
+                .map(|i| format!("Item: {}", i.text)),
+        }
+        .unwrap_or_else(|| "Something deleted".to_string());
+        let target_date = match milestone.target_date {
+            0 => String::new(),
+            ts => timestamp_to_string(ts),
+        };
+        let completed = match (milestone.completed, milestone.completed_on) {
+            (true, Some(ts)) => timestamp_to_string(ts),
+            (true, None) => "Yes".to_string(),
+            (false, _) => String::new(),
+        };
+        rendered.push_str(&format!(
+            "| {} | {} | {} | {} |\n",
+            table_cell(&milestone.title), table_cell(&tracks), target_date, completed
+        ));
+    }
+    rendered
+}
+
+/// Read a markdown document as projects: each # heading starts a project and
+/// each deeper heading an object in it, with `- [ ]` and `- [x]` tasks as
+/// items. Anything before the first # goes in a project called default_title.
+/// A Milestones table like the one written above becomes milestones.
+/// Records are numbered from 1 and created at `now`.
+pub fn notebook_from_markdown(contents: &str, default_title: &str, now: u64) -> Result<Notebook, String> {
+    let mut reader = MarkdownReader {
+        notebook: Notebook::default(),
+        default_title: default_title.to_string(),
+        now,
+        project: None,
+        object: None,
+        in_milestones: false,
+        in_code: false,
+    };
+
+    for (i, line) in contents.lines().enumerate() {
+        reader.line(line).map_err(|e| format!("line {}: {}", i + 1, e))?;
+    }
+
+    let mut notebook = reader.notebook;
+    for note in &mut notebook.notes {
+        note.description = note.description.trim().to_string();
+    }
+    for object in &mut notebook.objects {
+        object.description = object.description.trim().to_string();
+    }
+    if notebook.objects.is_empty() && notebook.notes.iter().all(|n| n.description.is_empty()) {
+        return Err("There are no headings or tasks in it".to_string());
+    }
+    Ok(notebook)
+}
+
+struct MarkdownReader {
+    notebook: Notebook,
+    default_title: String,
+    now: u64,
+    project: Option<usize>,   // Index of the project being read
+    object: Option<usize>,    // Index of the object being read
+    in_milestones: bool,      // Under a "## Milestones" heading
+    in_code: bool,            // Inside a ``` block
+}
+
+impl MarkdownReader {
+    fn line(&mut self, line: &str) -> Result<(), String> {
+        let trimmed = line.trim();
+
+        // Code blocks are kept as they are, in the description
+        if trimmed.starts_with("```") || self.in_code {
+            self.in_code ^= trimmed.starts_with("```");
+            self.describe(line);
+            return Ok(());
+        }
+
+        if let Some((level, title)) = markdown_heading(trimmed) {
+            let title = markdown_unescape(title);
+            self.in_milestones = level == 2 && title == "Milestones";
+            if level == 1 {
+                self.new_project(&title);
+            } else if !self.in_milestones {
+                self.new_object(&title);
+            }
+            return Ok(());
+        }
+
+        if let Some((completed, text)) = markdown_task(trimmed) {
+            self.new_item(completed, text);
+            return Ok(());
+        }
+
+        if self.in_milestones && trimmed.starts_with('|') {
+            return self.milestone_row(trimmed);
+        }
+        self.describe(line);
+        Ok(())
+    }
+
+    fn next_id(&self) -> usize {
+        self.notebook.notes.len() + self.notebook.objects.len() + self.notebook.items.len()
+            + self.notebook.milestones.len() + 1
+    }
+
+    fn new_project(&mut self, title: &str) -> usize {
+        self.notebook.notes.push(Funknote {
+            id: self.next_id(),
+            title: title.to_string(),
+            description: String::new(),
+            created_on: self.now,
+            active: true,
+            objects: Vec::new(),
+            milestones: Vec::new(),
+        });
+        self.object = None;
+        *self.project.insert(self.notebook.notes.len() - 1)
+    }
+
+    fn current_project(&mut self) -> usize {
+        match self.project {
+            Some(project) => project,
+            None => {
+                let title = self.default_title.clone();
+                self.new_project(&title)
+            }
+        }
+    }
+
+    fn new_object(&mut self, title: &str) -> usize {
+        let project = self.current_project();
+        let object = Object {
+            id: self.next_id(),
+            project_id: self.notebook.notes[project].id,
+            title: title.to_string(),
+            description: String::new(),
+            created_on: self.now,
+            active: true,
+            items: Vec::new(),
+        };
+        self.notebook.notes[project].objects.push(object.id);
+        self.notebook.objects.push(object);
+        *self.object.insert(self.notebook.objects.len() - 1)
+    }
+
+    // Tasks straight under a project heading go in an object of their own
+    fn new_item(&mut self, completed: bool, text: &str) {
+        let object = match self.object {
+            Some(object) if !self.in_milestones => object,
+            _ => self.new_object("Tasks"),
+        };
+        self.in_milestones = false;
+
+        // Our own export puts the completion date on the end
+        let (text, completed_on) = match text.strip_suffix(')').and_then(|t| t.rsplit_once(" (done ")) {
+            Some((text, date)) if completed => match string_to_timestamp(date) {
+                Some(ts) => (text, Some(ts)),
+                None => (text, None),
+            },
+            _ => (text, None),
+        };
+
+        let item = Item {
+            id: self.next_id(),
+            object_id: self.notebook.objects[object].id,
+            text: markdown_unescape(text),
+            created_on: self.now,
+            completed,
+            completed_on,
+        };
+        self.notebook.objects[object].items.push(item.id);
+        self.notebook.items.push(item);
+    }
+
+    // | Milestone | Tracks | Target date | Completed |
+    fn milestone_row(&mut self, row: &str) -> Result<(), String> {
+        let cells = table_cells(row);
+        let is_divider = cells.iter().all(|c| !c.is_empty() && c.chars().all(|ch| matches!(ch, '-' | ':')));
+        if is_divider || cells.first().is_some_and(|c| c == "Milestone") {
+            return Ok(());
+        }
+        let [title, tracks, target_date, completed] = cells.as_slice() else {
+            return Err(format!("A milestone row needs 4 cells, not {}", cells.len()));
+        };
+
+        let date = |text: &str| string_to_timestamp(text).ok_or_else(|| format!("'{}' isn't a YYYY-MM-DD date", text));
+        let project = self.current_project();
+        let project_id = self.notebook.notes[project].id;
+        let notebook = &self.notebook;
+        let in_project = |o: &&Object| o.project_id == project_id;
+
+        // Tracking something that isn't in the document falls back to the project
+        let target = if let Some(title) = tracks.strip_prefix("Object: ") {
+            notebook.objects.iter().filter(in_project).find(|o| o.title == *title)
+                .map(|o| MilestoneTarget::Object(o.id))
+        } else if let Some(text) = tracks.strip_prefix("Item: ") {
+            notebook.objects.iter().filter(in_project)
+                .flat_map(|o| notebook.items.iter().filter(move |i| i.object_id == o.id))
+                .find(|i| i.text == *text)
+                .map(|i| MilestoneTarget::Item(i.id))
+        } else {
+            None
+        }
+        .unwrap_or(MilestoneTarget::Project(project_id));
+
+        let milestone = Milestone {
+            id: self.next_id(),
+            project_id,
+            title: title.clone(),
+            description: String::new(),
+            target_date: if target_date.is_empty() { 0 } else { date(target_date)? },
+            completed: !completed.is_empty(),
+            completed_on: match completed.as_str() {
+                "" | "Yes" => None,
+                completed => Some(date(completed)?),
+            },
+            target,
+        };
+        self.notebook.notes[project].milestones.push(milestone.id);
+        self.notebook.milestones.push(milestone);
+        Ok(())
+    }
+
+    // Other text describes whatever heading it's under
+    fn describe(&mut self, line: &str) {
+        if self.in_milestones {
+            return;
+        }
+        let text = if self.in_code { line.to_string() } else { markdown_unescape(line.trim_end()) };
+        let description = match self.object {
+            Some(object) => &mut self.notebook.objects[object].description,
+            None if line.trim().is_empty() && self.project.is_none() => return,
+            None => {
+                let project = self.current_project();
+                &mut self.notebook.notes[project].description
+            }
+        };
+        description.push_str(&text);
+        description.push('\n');
+    }
+}
+
+// "## Beds" -> (2, "Beds")
+fn markdown_heading(line: &str) -> Option<(usize, &str)> {
+    let level = line.chars().take_while(|&c| c == '#').count();
+    let title = line[level..].strip_prefix(' ')?.trim().trim_end_matches('#').trim_end();
+    (1..=6).contains(&level).then_some((level, title))
+}
+
+// "- [x] Buy soil" -> (true, "Buy soil")
+fn markdown_task(line: &str) -> Option<(bool, &str)> {
+    let rest = line.strip_prefix("- ").or_else(|| line.strip_prefix("* ")).or_else(|| line.strip_prefix("+ "))?;
+    let completed = match rest.get(..3)? {
+        "[ ]" => false,
+        "[x]" | "[X]" => true,
+        _ => return None,
+    };
+    Some((completed, rest[3..].trim()))
+}
+
+// The cells of a table row, unescaped. \| is a | inside a cell.
+fn table_cells(row: &str) -> Vec<String> {
+    let row = row.trim().trim_start_matches('|');
+    let row = row.strip_suffix('|').filter(|r| !r.ends_with('\\')).unwrap_or(row);
+
+    let mut cells = vec![String::new()];
+    let mut chars = row.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                cells.last_mut().unwrap().push(c);
+                if let Some(next) = chars.next() {
+                    cells.last_mut().unwrap().push(next);
+                }
+            }
+            '|' => cells.push(String::new()),
+            c => cells.last_mut().unwrap().push(c),
+        }
+    }
+    cells.iter().map(|c| markdown_unescape(c.trim())).collect()
+}
+
+// A backslash before punctuation just means that character
+fn markdown_unescape(text: &str) -> String {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        match chars.peek() {
+            Some(&next) if c == '\\' && next.is_ascii_punctuation() => {
+                unescaped.push(next);
+                chars.next();
+            }
+            _ => unescaped.push(c),
+        }
+    }
+    unescaped
+}
+
+fn push_description(rendered: &mut String, description: &str) {
+    if !description.trim().is_empty() {
+        rendered.push('\n');
+        rendered.push_str(&markdown_escape(description.trim_end()));
+        rendered.push('\n');
+    }
+}
+
+// Text on one line (headings and list items can't span lines)
+fn markdown_line(text: &str) -> String {
+    markdown_escape(&text.split_whitespace().collect::<Vec<_>>().join(" "))
+}
+
+fn table_cell(text: &str) -> String {
+    markdown_line(text).replace('|', "\\|")
+}
+
+// Backslash the characters markdown would otherwise take as formatting
+fn markdown_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        if matches!(c, '\\' | '`' | '*' | '_' | '[' | ']' | '<' | '#') {
+            escaped.push('\\');
+        }
+        escaped.push(c);
+    }
+    escaped
+}
+
+// ====== CSV =====
+// Every item and milestone as a row, for spreadsheets. Milestones give the
+// object they track (if any) and have no created date.
+const CSV_HEADER: [&str; 8] = [
+    "Type", "Project", "Object", "Text", "Created", "Target date", "Completed", "Completed on",
+];
+
+/// Render every item and milestone as CSV, one row each, in project order.
+pub fn notebook_to_csv(notebook: &Notebook) -> String {
+    let date = |ts: u64| if ts == 0 { String::new() } else { timestamp_to_string(ts) };
+    let mut rows: Vec<Vec<String>> = vec![CSV_HEADER.iter().map(|h| h.to_string()).collect()];
+
+    for note in &notebook.notes {
+        for object in notebook.objects.iter().filter(|o| note.objects.contains(&o.id)) {
+            for item in notebook.items.iter().filter(|i| object.items.contains(&i.id)) {
+                rows.push(vec![
+                    "item".to_string(),
+                    note.title.clone(),
+                    object.title.clone(),
+                    item.text.clone(),
+                    date(item.created_on),
+                    String::new(),
+                    item.completed.to_string(),
+                    item.completed_on.map(date).unwrap_or_default(),
+                ]);
+            }
+        }
+
+        for milestone in notebook.milestones.iter().filter(|m| note.milestones.contains(&m.id)) {
+            let object_id = match milestone.target {
+                MilestoneTarget::Project(_) => None,
+                MilestoneTarget::Object(id) => Some(id),
+                MilestoneTarget::Item(id) => notebook.items.iter().find(|i| i.id == id).map(|i| i.object_id),
+            };
+            let object = object_id
+                .and_then(|id| notebook.objects.iter().find(|o| o.id == id))
+                .map(|o| o.title.clone())
+                .unwrap_or_default();
+            rows.push(vec![
+                "milestone".to_string(),
+                note.title.clone(),
+                object,
+                milestone.title.clone(),
+                String::new(),
+                date(milestone.target_date),
+                milestone.completed.to_string(),
+                milestone.completed_on.map(date).unwrap_or_default(),
+            ]);
+        }
+    }
+
+    rows.iter()
+        .map(|row| row.iter().map(|field| csv_field(field)).collect::<Vec<_>>().join(","))
+        .map(|line| line + "\r\n")
+        .collect()
+}
+
+// Fields with commas, quotes, line breaks or edge spaces go in quotes,
+// with any quotes inside doubled
+fn csv_field(field: &str) -> String {
+    let needs_quotes = field.contains([',', '"', '\n', '\r']) || field.trim() != field;
+    if needs_quotes {
+        format!("\"{}\"", field.replace('"', "\"\""))
+    } else {
+        field.to_string()
+    }
+}
+
+// ====== JSON VALUES =====
+// Just enough JSON for the export. Numbers are kept as written, so big IDs
+// and timestamps come through exactly.
+#[derive(Debug, Clone, PartialEq)]
+enum Json {
+    Null,
+    Bool(bool),
+    Number(String),
+    String(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),   // In the order written
+}
+
+// Two spaces per level, with empty lists kept on one line
+fn render_json(value: &Json, depth: usize, out: &mut String) {
+    let indent = |depth: usize| "  ".repeat(depth);
+    match value {
+        Json::Null => out.push_str("null"),
+        Json::Bool(b) => out.push_str(if *b { "true" } else { "false" }),
+        Json::Number(n) => out.push_str(n),
+        Json::String(s) => render_json_string(s, out),
+        Json::Array(values) if values.is_empty() => out.push_str("[]"),
+        Json::Array(values) => {
+            out.push_str("[\n");
+            for (i, value) in values.iter().enumerate() {
+                out.push_str(&indent(depth + 1));
+                render_json(value, depth + 1, out);
+                out.push_str(if i + 1 < values.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&indent(depth));
+            out.push(']');
+        }
+        Json::Object(fields) => {
+            out.push_str("{\n");
+            for (i, (key, value)) in fields.iter().enumerate() {
+                out.push_str(&indent(depth + 1));
+                render_json_string(key, out);
+                out.push_str(": ");
+                render_json(value, depth + 1, out);
+                out.push_str(if i + 1 < fields.len() { ",\n" } else { "\n" });
+            }
+            out.push_str(&indent(depth));
+            out.push('}');
+        }
+    }
+}
+
+fn render_json_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+// Errors say where in the text they are, as "line L, column C: ..."
+fn parse_json(text: &str) -> Result<Json, String> {
+    let mut parser = JsonParser { text, pos: 0 };
+    let value = parser.value()?;
+    parser.skip_whitespace();
+    if parser.pos < text.len() {
+        return Err(parser.error("Unexpected text after the end of the JSON"));
+    }
+    Ok(value)
+}
+
+struct JsonParser<'a> {
+    text: &'a str,
+    pos: usize,   // Byte offset of the next character
+}
+
+impl JsonParser<'_> {
+    fn error(&self, message: &str) -> String {
+        let before = &self.text[..self.pos];
+        let line = before.matches('\n').count() + 1;
+        let column = before.rsplit('\n').next().unwrap_or_default().chars().count() + 1;
+        format!("line {}, column {}: {}", line, column, message)
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.text[self.pos..].chars().next()
+    }
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.peek()?;
+        self.pos += c.len_utf8();
+        Some(c)
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| matches!(c, ' ' | '\t' | '\n' | '\r')) {
+            self.pos += 1;
+        }
+    }
+
+    fn expect(&mut self, expected: char) -> Result<(), String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some(c) if c == expected => {
+                self.pos += 1;
+                Ok(())
+            }
+            _ => Err(self.error(&format!("Expected '{}'", expected))),
+        }
+    }
+
+    fn value(&mut self) -> Result<Json, String> {
+        self.skip_whitespace();
+        match self.peek() {
+            Some('{') => self.object(),
+            Some('[') => self.array(),
+            Some('"') => self.string().map(Json::String),
+            Some('-' | '0'..='9') => self.number(),
+            Some(_) => {
+                for (word, value) in [("true", Json::Bool(true)), ("false", Json::Bool(false)), ("null", Json::Null)] {
+                    if self.text[self.pos..].starts_with(word) {
+                        self.pos += word.len();
+                        return Ok(value);
+                    }
+                }
+                Err(self.error("Expected a JSON value"))
+            }
+            None => Err(self.error("The JSON ends too soon")),
+        }
+    }
+
+    fn object(&mut self) -> Result<Json, String> {
+        self.expect('{')?;
+        let mut fields = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some('}') {
+            self.pos += 1;
+            return Ok(Json::Object(fields));
+        }
+        loop {
+            self.skip_whitespace();
+            if self.peek() != Some('"') {
+                return Err(self.error("Expected a quoted field name"));
+            }
+            let key = self.string()?;
+            self.expect(':')?;
+            fields.push((key, self.value()?));
+
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some('}') => {
+                    self.pos += 1;
+                    return Ok(Json::Object(fields));
+                }
+                _ => return Err(self.error("Expected ',' or '}'")),
+            }
+        }
+    }
+
+    fn array(&mut self) -> Result<Json, String> {
+        self.expect('[')?;
+        let mut values = Vec::new();
+        self.skip_whitespace();
+        if self.peek() == Some(']') {
+            self.pos += 1;
+            return Ok(Json::Array(values));
+        }
+        loop {
+            values.push(self.value()?);
+            self.skip_whitespace();
+            match self.peek() {
+                Some(',') => self.pos += 1,
+                Some(']') => {
+                    self.pos += 1;
+                    return Ok(Json::Array(values));
+                }
+                _ => return Err(self.error("Expected ',' or ']'")),
+            }
+        }
+    }
+
+    fn number(&mut self) -> Result<Json, String> {
+        let start = self.pos;
+        while self.peek().is_some_and(|c| c.is_ascii_digit() || matches!(c, '-' | '+' | '.' | 'e' | 'E')) {
+            self.pos += 1;
+        }
+        let number = &self.text[start..self.pos];
+        if number.parse::<f64>().is_err() {
+            self.pos = start;
+            return Err(self.error(&format!("'{}' isn't a number", number)));
+        }
+        Ok(Json::Number(number.to_string()))
+    }
+
+    fn string(&mut self) -> Result<String, String> {
+        self.expect('"')?;
+        let mut s = String::new();
+        loop {
+            let escape_at = self.pos;
+            match self.next() {
+                None => return Err(self.error("The string is never closed")),
+                Some('"') => return Ok(s),
+                Some('\\') => match self.next() {
+                    Some('"') => s.push('"'),
+                    Some('\\') => s.push('\\'),
+                    Some('/') => s.push('/'),
+                    Some('b') => s.push('\u{8}'),
+                    Some('f') => s.push('\u{c}'),
+                    Some('n') => s.push('\n'),
+                    Some('r') => s.push('\r'),
+                    Some('t') => s.push('\t'),
+                    Some('u') => {
+                        let c = self.unicode_escape().ok_or_else(|| {
+                            self.pos = escape_at;
+                            self.error("Bad \\u escape")
+                        })?;
+                        s.push(c);
+                    }
+                    _ => {
+                        self.pos = escape_at;
+                        return Err(self.error("Unknown escape"));
+                    }
+                },
+                Some(c) if (c as u32) < 0x20 => {
+                    self.pos = escape_at;
+                    return Err(self.error("Control characters have to be escaped in strings"));
+                }
+                Some(c) => s.push(c),
+            }
+        }
+    }
+
+    // The XXXX of \uXXXX, plus the second half of a surrogate pair
+    fn unicode_escape(&mut self) -> Option<char> {
+        let first = hex_at(self.text, &mut self.pos)?;
+        if !(0xD800..0xDC00).contains(&first) {
+            return char::from_u32(first);
+        }
+        if !self.text[self.pos..].starts_with("\\u") {
+            return None;
+        }
+        self.pos += 2;
+        let second = hex_at(self.text, &mut self.pos)?;
+        if !(0xDC00..0xE000).contains(&second) {
+            return None;
+        }
+        char::from_u32(0x10000 + ((first - 0xD800) << 10) + (second - 0xDC00))
+    }
+}
+
+// Four hex digits at pos, moving pos past them
+fn hex_at(text: &str, pos: &mut usize) -> Option<u32> {
+    let value = u32::from_str_radix(text.get(*pos..*pos + 4)?, 16).ok()?;
+    *pos += 4;
+    Some(value)
+}
+
+// ================== TESTS ===================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::fixtures::sample_notebook;
+
+    // The sample with text that needs escaping in every format
+    fn quoted_notebook() -> Notebook {
+        let mut notebook = sample_notebook();
+        notebook.notes[0].title = "Garden \"2026\"".to_string();
+        notebook.notes[0].description = "Line one\nLine two".to_string();
+        notebook.items[0].text = "Buy soil \\ compost ✓".to_string();
+        notebook
+    }
+
+    #[test]
+    fn test_json_round_trip() {
+        let notebook = quoted_notebook();
+        let json = notebook_to_json(&notebook);
+        assert!(json.contains("\"title\": \"Garden \\\"2026\\\"\""));
+        assert_eq!(notebook_from_json(&json), Ok(notebook));
+    }
+
+    #[test]
+    fn test_renumber_keeps_links() {
+        let mut notebook = quoted_notebook();
+        let mut next = 100;
+        renumber(&mut notebook, || {
+            next += 1;
+            Ok(next)
+        })
+        .unwrap();
+
+        assert_eq!(notebook.notes[0].id, 101);
+        assert_eq!(notebook.notes[0].objects, vec![102]);
+        assert_eq!((notebook.objects[0].project_id, notebook.objects[0].items.clone()), (101, vec![103]));
+        assert_eq!(notebook.items[0].object_id, 102);
+        assert_eq!(notebook.milestones[0].id, 104);
+        assert_eq!(notebook.milestones[0].target, MilestoneTarget::Item(103));
+
+        // A link out of the notebook would keep an ID the store may already use
+        let mut notebook = sample_notebook();
+        notebook.milestones[1].target = MilestoneTarget::Object(42);
+        let err = renumber(&mut notebook, || {
+            next += 1;
+            Ok(next)
+        })
+        .unwrap_err();
+        assert_eq!(err, "ID 42 links to a record that isn't being imported");
+    }
+
+    #[test]
+    fn test_markdown_checklist() {
+        let mut notebook = quoted_notebook();
+        notebook.items.push(Item {
+            id: 5,
+            object_id: 2,
+            text: "Water *daily*".to_string(),
+            created_on: 0,
+            completed: false,
+            completed_on: None,
+        });
+        notebook.objects[0].items.push(5);
+        notebook.milestones[0].title = "Soil | compost".to_string();
+
+        let markdown = project_to_markdown(&notebook.notes[0], &notebook);
+        assert_eq!(markdown, "# Garden \"2026\"\n\
+                              \nLine one\nLine two\n\
+                              \n## Beds\n\
+                              \n- [x] Buy soil \\\\ compost ✓ (done 2025-12-02)\n\
+                              - [ ] Water \\*daily\\*\n\
+                              \n## Milestones\n\
+                              \n| Milestone | Tracks | Target date | Completed |\n\
+                              | --- | --- | --- | --- |\n\
+                              | Soil \\| compost | Item: Buy soil \\\\ compost ✓ | 2026-01-01 |  |\n\
+                              | Harvest | Whole project |  |  |\n");
+    }
+
+    #[test]
+    fn test_markdown_import() {
+        let document = "Loose notes up top\n\
+                        - [ ] Stray task\n\
+                        \n# Garden\n\
+                        \nThe *whole* plot\n\
+                        \n## Beds ##\n\
+                        - [x] Buy soil \\\\ compost (done 2025-12-02)\n\
+                        - [X] Dig\n\
+                        \x20 - [ ] Weed (nested)\n\
+                        - plain bullet\n\
+                        - [y] not a task\n\
+                        \n## Milestones\n\
+                        \n| Milestone | Tracks | Target date | Completed |\n\
+                        | --- | --- | --- | --- |\n\
+                        | Soil \\| compost | Item: Buy soil \\\\ compost | 2026-01-01 | |\n\
+                        | Plan | Whole project | | Yes |\n";
+        let notebook = notebook_from_markdown(document, "todo", 99).unwrap();
+
+        let titles: Vec<&str> = notebook.notes.iter().map(|n| n.title.as_str()).collect();
+        assert_eq!(titles, vec!["todo", "Garden"]);
+        assert_eq!(notebook.notes[0].description, "Loose notes up top");
+        assert_eq!(notebook.notes[1].description, "The *whole* plot");
+        let objects: Vec<&str> = notebook.objects.iter().map(|o| o.title.as_str()).collect();
+        assert_eq!(objects, vec!["Tasks", "Beds"]);
+        assert_eq!(notebook.objects[1].description, "- plain bullet\n- [y] not a task");
+
+        let items: Vec<(&str, bool, Option<u64>)> = notebook.items.iter()
+            .map(|i| (i.text.as_str(), i.completed, i.completed_on))
+            .collect();
+        assert_eq!(items, vec![
+            ("Stray task", false, None),
+            ("Buy soil \\ compost", true, Some(1764633600)),
+            ("Dig", true, None),
+            ("Weed (nested)", false, None),
+        ]);
+
+        let soil = &notebook.milestones[0];
+        assert_eq!(soil.title, "Soil | compost");
+        assert_eq!(soil.target, MilestoneTarget::Item(notebook.items[1].id));
+        assert_eq!(soil.target_date, 1767225600);
+        let plan = &notebook.milestones[1];
+        assert_eq!((plan.completed, plan.target.clone()), (true, MilestoneTarget::Project(notebook.notes[1].id)));
+
+        let err = notebook_from_markdown("# P\n## Milestones\n| M | Whole project | tomorrow | |\n", "x", 0);
+        assert_eq!(err.unwrap_err(), "line 3: 'tomorrow' isn't a YYYY-MM-DD date");
+        assert!(notebook_from_markdown("just some text?\n", "x", 0).is_ok());
+        assert!(notebook_from_markdown("\n\n", "x", 0).is_err());
+    }
+
+    #[test]
+    fn test_markdown_round_trip() {
+        let notebook = quoted_notebook();
+        let markdown = project_to_markdown(&notebook.notes[0], &notebook);
+        let imported = notebook_from_markdown(&markdown, "x", 0).unwrap();
+        assert_eq!(project_to_markdown(&imported.notes[0], &imported), markdown);
+    }
+
+    #[test]
+    fn test_csv_rows_and_quoting() {
+        let mut notebook = quoted_notebook();
+        notebook.objects[0].title = "Beds, raised".to_string();
+
+        let csv = notebook_to_csv(&notebook);
+        let lines: Vec<&str> = csv.split("\r\n").collect();
+        assert_eq!(lines[0], "Type,Project,Object,Text,Created,Target date,Completed,Completed on");
+        assert_eq!(
+            lines[1],
+            "item,\"Garden \"\"2026\"\"\",\"Beds, raised\",Buy soil \\ compost ✓,2025-12-02,,true,2025-12-02"
+        );
+        assert_eq!(lines[2], "milestone,\"Garden \"\"2026\"\"\",\"Beds, raised\",Soil in,,2026-01-01,false,");
+        assert_eq!(lines[3], "milestone,\"Garden \"\"2026\"\"\",,Harvest,,,false,");
+        assert_eq!(lines[4], "");
+        assert_eq!(csv_field("two\nlines"), "\"two\nlines\"");
+        assert_eq!(csv_field(" padded"), "\" padded\"");
+    }
+
+    #[test]
+    fn test_json_import_errors() {
+        // Other tools can leave out anything but the IDs and names
+        let minimal = r#"{"funknotes": 1, "projects": [{"id": 7, "title": "🌱 Seeds",
+                          "objects": [{"id": 8, "title": "Tray", "items": [{"id": 9, "text": "Sow"}]}]}]}"#;
+        let notebook = notebook_from_json(minimal).unwrap();
+        assert_eq!(notebook.notes[0].title, "🌱 Seeds");
+        assert!(notebook.objects[0].active && !notebook.items[0].completed);
+
+        let err = notebook_from_json("{\"funknotes\": 1,\n \"projects\": [,]}").unwrap_err();
+        assert!(err.starts_with("line 2, column 15:"), "{}", err);
+        let err = notebook_from_json(r#"{"funknotes": 1, "projects": [{"id": 1}]}"#).unwrap_err();
+        assert_eq!(err, "projects[0]: \"title\" is missing");
+        let err = notebook_from_json(r#"{"funknotes": 1, "projects": [{"id": -1, "title": "x"}]}"#).unwrap_err();
+        assert!(err.contains("whole number"), "{}", err);
+        let dangling = r#"{"funknotes": 1, "projects": [{"id": 1, "title": "x",
+                           "milestones": [{"id": 2, "title": "m", "target": {"kind": "item", "id": 5}}]}]}"#;
+        assert!(notebook_from_json(dangling).unwrap_err().contains("isn't in the file"));
+        assert!(notebook_from_json(r#"{"funknotes": 2, "projects": []}"#).is_err());
+    }
+}
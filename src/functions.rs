@@ -1,24 +1,48 @@
-use crate::interpret::ASTNode;
-use crate::operations::{self, *};
-use std::collections::HashMap;
+use crate::interpret::{ASTNode, InterpretError};
+use crate::operations::{self, RecordRef, Target};
+use crate::store::Store;
+use crate::types::{FunkState, NodeKind};
+use std::collections::{BTreeMap, HashMap};
 
 // Argument specification for a method parameter
 #[derive(Clone, Debug)]
 pub enum ArgSpec {
     Identifier(Vec<String>),  // Must be one of these identifiers
     Literal,                   // Must be a quoted string
+    Integer,                   // Must be a whole number, like an ID
+    Boolean,                   // Must be true or false
+    Date,                      // Must be a YYYY-MM-DD date
     Either(Vec<ArgSpec>),      // Must match ANY of these specs
-    Any,                       // Any ASTNode type
+}
+
+// Keyword arguments by name, with defaults filled in
+type NamedArgs = BTreeMap<String, ASTNode>;
+
+// What a chain of calls is working on, handed from each call to the next.
+// A call that makes or changes a record makes it the subject, so the calls
+// after it can leave out which record they mean.
+#[derive(Debug, Default)]
+pub struct Context {
+    pub subject: Option<(NodeKind, usize)>,
 }
 
 // Type alias for method functions
-type MethodFn = fn(&[ASTNode]) -> Result<(), String>;
+type MethodFn = fn(&[ASTNode], &NamedArgs, &mut Context, &mut FunkState, &mut dyn Store) -> Result<(), String>;
+
+// A parameter given as `name = value`, in any order after the positional ones
+#[derive(Clone, Debug)]
+pub struct NamedArg {
+    name: String,
+    spec: ArgSpec,
+    default: Option<ASTNode>,  // Used when the call leaves it out
+}
 
 #[derive(Clone, Debug)]
 pub struct MethodSignature {
     name: String,
     required_args: Vec<ArgSpec>,
     optional_args: Vec<ArgSpec>,
+    named_args: Vec<NamedArg>,
     help_text: String,
     examples: Vec<String>,
     func: MethodFn,
@@ -33,11 +57,12 @@ impl MethodRegistry {
         let mut registry = Self {
             methods: HashMap::new(),
         };
-        
-        // Register 'new' with Either variant to accept identifier OR literal as first arg
-        registry.register_with_spec(
-            "new",
-            vec![
+
+        // Register 'new' with Either variant to accept identifier OR literal as first arg.
+        // It takes keyword parameters too, so it's registered with a full signature.
+        registry.register(MethodSignature {
+            name: "new".to_string(),
+            required_args: vec![
                 // First arg can be either an identifier (type) OR a literal (name)
                 ArgSpec::Either(vec![
                     ArgSpec::Identifier(vec![
@@ -49,99 +74,388 @@ impl MethodRegistry {
                     ArgSpec::Literal,
                 ]),
             ],
-            vec![
+            optional_args: vec![
                 // Second arg is optional, must be literal (name when type is specified)
                 ArgSpec::Literal,
             ],
-            "Creates a new note of the specified type",
-            vec![
+            named_args: vec![
+                NamedArg { name: "due".to_string(), spec: ArgSpec::Date, default: None },
+                NamedArg { name: "select".to_string(), spec: ArgSpec::Boolean, default: Some(ASTNode::Boolean(true)) },
+            ],
+            help_text: "Creates a new note of the specified type. Projects and objects become current unless select=false."
+                .to_string(),
+            examples: vec![
                 "new(\"My Project\")              # Creates a project (default)".to_string(),
-                "new(object, \"My Object\")       # Creates an object".to_string(),
-                "new(milestone, \"Release 1.0\")  # Creates a milestone".to_string(),
+                "new(object, \"My Object\")       # Creates an object in the current project".to_string(),
+                "new(item, \"Write docs\")        # Creates an item in the current object".to_string(),
+                "new(milestone, \"Release 1.0\")  # Creates a milestone in the current project".to_string(),
+                "new(milestone, \"Release 1.0\", due = 2026-11-01)".to_string(),
+                "new(object, \"Later\", select = false)   # Stays where you are".to_string(),
             ],
-            method_new
-        );
+            func: method_new,
+        });
 
         registry.register_with_spec(
             "show_project",
+            vec![],
             vec![
-                // First arg must be literal (name)
+                // Optional name, defaults to the current or primary project
                 ArgSpec::Literal,
             ],
-            vec![],
-            "Displays the specified project by name.",
+            "Displays the specified project by name, or the current (else primary) project.",
             vec![
                 "show_project(\"My Project\")      # Displays the project".to_string(),
+                "show_project()                  # Displays the current or primary project".to_string(),
             ],
             show_project
         );
-       
+
         registry.register_with_spec(
             "delete",
             vec![
                 // First arg either ident or literal
                 ArgSpec::Either(vec![
                     ArgSpec::Identifier(vec![
-                    "project".to_string(),
-                    "object".to_string(),
-                    "item".to_string(),
-                    "milestone".to_string(),
+                        "project".to_string(),
+                        "object".to_string(),
+                        "item".to_string(),
+                        "milestone".to_string(),
+                    ]),
+                    ArgSpec::Literal,
                 ]),
+            ],
+            vec![
+                // Optional second arg is the name, or `force` after a bare name
+                ArgSpec::Either(vec![
+                    ArgSpec::Literal,
+                    ArgSpec::Identifier(vec!["force".to_string()]),
+                ]),
+                ArgSpec::Identifier(vec!["force".to_string()]),
+            ],
+            "Moves the specified type with the specified name to the trash. Needs `force` if anything is under it.",
+            vec![
+                "delete(\"My Project\")                # Deletes project (default)".to_string(),
+                "delete(object, \"My Object\")         # Deletes object".to_string(),
+                "delete(milestone, \"Release 1.0\")    # Delete milestone".to_string(),
+                "delete(project, \"My Project\", force) # Deletes project and everything in it".to_string(),
+            ],
+            method_delete
+        );
+
+        registry.register_with_spec(
+            "restore",
+            vec![
+                // First arg either ident or literal
+                ArgSpec::Either(vec![
+                    ArgSpec::Identifier(vec![
+                        "project".to_string(),
+                        "object".to_string(),
+                        "item".to_string(),
+                        "milestone".to_string(),
+                    ]),
+                    ArgSpec::Literal,
+                ]),
+            ],
+            vec![
+                // Optional second arg must be literal
                 ArgSpec::Literal,
-            ]),
-        ],
-        vec![
-            // Optional second arg must be literal
-            ArgSpec::Literal,
-        ],
-        "Deletes the specified type with the specified name.",
-        vec![
-            "delete(\"My Project\")             # Deletes project (default)".to_string(),
-            "delete(object, \"My Object\")      # Deletes obejct".to_string(),
-            "delete(milestone, \"Release 1.0\") # Delete milstone".to_string(),
-        ],
-        method_delete
-        
+            ],
+            "Brings a deleted record, and everything deleted with it, back from the trash.",
+            vec![
+                "restore(\"My Project\")            # Restores whatever was deleted under that name".to_string(),
+                "restore(object, \"My Object\")     # Restores only an object".to_string(),
+            ],
+            method_restore
         );
 
         registry.register_with_spec(
             "list",
+            vec![],
             vec![
-                // First arg either ident or literal
+                // Optional first arg either ident or literal
                 ArgSpec::Either(vec![
                     ArgSpec::Identifier(vec![
-                    "project".to_string(),
-                    "object".to_string(),
-                    "item".to_string(),
-                    "milestone".to_string(),
+                        "project".to_string(),
+                        "object".to_string(),
+                        "item".to_string(),
+                        "milestone".to_string(),
+                    ]),
+                    ArgSpec::Literal,
                 ]),
+                // Optional second arg must be literal
                 ArgSpec::Literal,
-            ]),
-        ],
-        vec![
-            // Optional second arg must be literal
-            ArgSpec::Literal,
-        ],
-        "Lists all the children of the specified identifier.",
-        vec![
-            "list(\"My Project\")             # Lists children of project (default)".to_string(),
-            "list(object, \"My Object\")      # Lists obeject".to_string(),
-            "list(milestone, \"Release 1.0\") # Lists milstone".to_string(),
-        ],
-        method_list
+            ],
+            "Lists the children of the specified record, or every record of a type.",
+            vec![
+                "list()                         # Lists children of the current or primary project".to_string(),
+                "list(\"My Project\")             # Lists children of project (default)".to_string(),
+                "list(object, \"My Object\")      # Lists the items of an object".to_string(),
+                "list(project)                  # Lists every project".to_string(),
+                "list(milestone)                # Lists milestones of the current project".to_string(),
+            ],
+            method_list
         );
 
         registry.register_with_spec(
             "title",
+            vec![
+                // Either the new title, or the type of the record to rename
+                ArgSpec::Either(vec![
+                    ArgSpec::Identifier(vec![
+                        "project".to_string(),
+                        "object".to_string(),
+                        "item".to_string(),
+                        "milestone".to_string(),
+                    ]),
+                    ArgSpec::Literal,
+                ]),
+            ],
+            vec![
+                // Then its current name and the new title
+                ArgSpec::Literal,
+                ArgSpec::Literal,
+            ],
+            "Sets the title of the named record, else of the one just made or changed, else of the current selection",
+            vec![
+                "title(\"My Amazing Project\")                  # Renames the current selection".to_string(),
+                "title(item, \"Buy soil\", \"Buy compost\")     # Renames an item".to_string(),
+            ],
+            method_title
+        );
+
+        registry.register_with_spec(
+            "describe",
+            vec![
+                // Either the new description, or the type of the record to describe
+                ArgSpec::Either(vec![
+                    ArgSpec::Identifier(vec![
+                        "project".to_string(),
+                        "object".to_string(),
+                        "milestone".to_string(),
+                    ]),
+                    ArgSpec::Literal,
+                ]),
+            ],
+            vec![
+                // Then its name and the new description
+                ArgSpec::Literal,
+                ArgSpec::Literal,
+            ],
+            "Sets the description of the named record, else of the one just made or changed, else of the current selection",
+            vec![
+                "describe(\"Everything for the spring garden\")           # Describes the current selection".to_string(),
+                "describe(milestone, \"Release 1.0\", \"First public build\")".to_string(),
+            ],
+            method_describe
+        );
+
+        registry.register_with_spec(
+            "primary",
+            vec![],
+            vec![
+                // A project name, or `none` to clear it
+                ArgSpec::Either(vec![
+                    ArgSpec::Literal,
+                    ArgSpec::Identifier(vec!["none".to_string()]),
+                ]),
+            ],
+            "Marks a project as primary: the default whenever no project is selected. Shows it with no args.",
+            vec![
+                "primary(\"My Project\")           # Makes it the default project".to_string(),
+                "primary()                       # Shows the primary project".to_string(),
+                "primary(none)                   # Clears it".to_string(),
+            ],
+            method_primary
+        );
+
+        registry.register_with_spec(
+            "use",
+            vec![
+                // First arg either ident or literal
+                ArgSpec::Either(vec![
+                    ArgSpec::Identifier(vec![
+                        "project".to_string(),
+                        "object".to_string(),
+                    ]),
+                    ArgSpec::Literal,
+                ]),
+            ],
+            vec![
+                // Optional second arg must be literal
+                ArgSpec::Literal,
+            ],
+            "Selects a project or object, so later commands act on it. Remembered between runs.",
+            vec![
+                "use(\"My Project\")              # Selects a project (default)".to_string(),
+                "use(object, \"My Object\")       # Selects an object in the current project".to_string(),
+            ],
+            method_use
+        );
+
+        registry.register_with_spec(
+            "cd",
             vec![ArgSpec::Literal],
             vec![],
-            "Sets the title of the current note",
+            "Moves into an object of the current project (or a project from the root). \"..\" goes up.",
             vec![
-                "title(\"My Amazing Project\")".to_string(),
+                "cd(\"My Object\")".to_string(),
+                "cd(\"..\")".to_string(),
             ],
-            method_title
+            method_cd
+        );
+
+        registry.register_with_spec(
+            "up",
+            vec![],
+            vec![],
+            "Moves up one level: from an object to its project, or from a project to the root",
+            vec![
+                "up()".to_string(),
+            ],
+            method_up
+        );
+
+        registry.register_with_spec(
+            "root",
+            vec![],
+            vec![],
+            "Clears the selection, going back to the root",
+            vec![
+                "root()".to_string(),
+            ],
+            method_root
+        );
+
+        registry.register_with_spec(
+            "where",
+            vec![],
+            vec![],
+            "Shows the currently selected project and object",
+            vec![
+                "where()".to_string(),
+            ],
+            method_where
+        );
+
+        registry.register_with_spec(
+            "back",
+            vec![],
+            vec![],
+            "Goes back to the previously selected project or object",
+            vec![
+                "back()".to_string(),
+            ],
+            method_back
+        );
+
+        registry.register_with_spec(
+            "forward",
+            vec![],
+            vec![],
+            "Goes forward again after back()",
+            vec![
+                "forward()".to_string(),
+            ],
+            method_forward
+        );
+
+        registry.register_with_spec(
+            "history",
+            vec![],
+            vec![],
+            "Lists the places visited, marking where you are now",
+            vec![
+                "history()".to_string(),
+            ],
+            method_history
+        );
+
+        registry.register_with_spec(
+            "migrate",
+            vec![],
+            vec![ArgSpec::Identifier(vec!["dry_run".to_string()])],
+            "Upgrades the notes file to the current format. dry_run only reports what would change",
+            vec![
+                "migrate(dry_run)   # See what would change".to_string(),
+                "migrate()          # Upgrade, keeping a backup of the old file".to_string(),
+            ],
+            method_migrate
+        );
+
+        registry.register_with_spec(
+            "check",
+            vec![],
+            vec![],
+            "Validates the whole notes file, reporting problems with their line and column",
+            vec!["check()".to_string()],
+            method_check
+        );
+
+        registry.register_with_spec(
+            "export",
+            vec![
+                ArgSpec::Identifier(vec!["json".to_string(), "markdown".to_string(), "csv".to_string()]),
+                ArgSpec::Literal,
+            ],
+            vec![ArgSpec::Literal],
+            "Writes every project, or just the one named, with everything under it to a file",
+            vec![
+                "export(json, \"notes.json\")".to_string(),
+                "export(markdown, \"Garden\", \"garden.md\")   # A checklist document".to_string(),
+                "export(csv, \"tasks.csv\")   # Every item and milestone, for spreadsheets".to_string(),
+            ],
+            method_export
+        );
+
+        registry.register_with_spec(
+            "import",
+            vec![
+                ArgSpec::Identifier(vec!["json".to_string(), "markdown".to_string()]),
+                ArgSpec::Literal,
+            ],
+            vec![],
+            "Adds the projects in a file to the notes. Everything imported gets a new ID",
+            vec![
+                "import(json, \"notes.json\")".to_string(),
+                "import(markdown, \"TODO.md\")   # Headings become projects and objects, - [ ] tasks items".to_string(),
+            ],
+            method_import
+        );
+
+        registry.register_with_spec(
+            "done",
+            vec![],
+            vec![
+                // The item's ID or text; left out, the item the chain is working on
+                ArgSpec::Either(vec![ArgSpec::Integer, ArgSpec::Literal, ArgSpec::Boolean]),
+                ArgSpec::Boolean,
+            ],
+            "Marks an item done, or not done with false. With no item, the one just made or changed",
+            vec![
+                "done(\"Buy soil\")        # Marks the item done".to_string(),
+                "done(12)                # The item with ID 12".to_string(),
+                "done(12, false)         # Not done after all".to_string(),
+                ".new(item, \"Write docs\").done()   # The item just made".to_string(),
+            ],
+            method_done
+        );
+
+        registry.register_with_spec(
+            "due",
+            vec![
+                // The milestone's ID or title, then the date; or just the date
+                // for the milestone the chain is working on
+                ArgSpec::Either(vec![ArgSpec::Integer, ArgSpec::Literal, ArgSpec::Date]),
+            ],
+            vec![ArgSpec::Date],
+            "Sets the target date of a milestone. With no milestone, the one just made or changed",
+            vec![
+                "due(\"Release 1.0\", 2026-11-01)".to_string(),
+                "due(7, 2027-01-15)      # The milestone with ID 7".to_string(),
+                ".new(milestone, \"Beta\").due(2026-12-01)".to_string(),
+            ],
+            method_due
         );
-       
 
         // Add method registries above here.
         registry
@@ -156,36 +470,37 @@ impl MethodRegistry {
         examples: Vec<String>,
         func: MethodFn,
     ) {
-        self.methods.insert(
-            name.to_string(),
-            MethodSignature {
-                name: name.to_string(),
-                required_args,
-                optional_args,
-                help_text: help_text.to_string(),
-                examples,
-                func,
-            },
-        );
+        self.register(MethodSignature {
+            name: name.to_string(),
+            required_args,
+            optional_args,
+            named_args: vec![],
+            help_text: help_text.to_string(),
+            examples,
+            func,
+        });
+    }
+
+    // For methods that need more than register_with_spec covers, like keyword parameters
+    pub fn register(&mut self, signature: MethodSignature) {
+        self.methods.insert(signature.name.clone(), signature);
     }
 
-    pub fn execute(&self, name: &str, args: &[ASTNode]) -> Result<(), String> {
+    pub fn execute(&self, name: &str, args: &[ASTNode], kwargs: &NamedArgs, context: &mut Context, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
         match self.methods.get(name) {
             Some(signature) => {
                 // Check if the first argument is the help identifier
-                if args.len() == 1 {
-                    if let ASTNode::Identifier(id) = &args[0] {
-                        if id == "help" {
-                            // Print help instead of executing
-                            self.print_help(signature);
-                            return Ok(());
-                        }
-                    }
+                if let [ASTNode::Identifier(id)] = args
+                    && id == "help"
+                {
+                    // Print help instead of executing
+                    self.print_help(signature);
+                    return Ok(());
                 }
                 
                 // Normal execution path - validate then execute
-                self.validate_args(signature, args)?;
-                (signature.func)(args)
+                let named = self.validate_args(signature, args, kwargs)?;
+                (signature.func)(args, &named, context, state, store)
             }
             None => Err(format!("Unknown method: {}", name)),
         }
@@ -226,6 +541,16 @@ impl MethodRegistry {
         for spec in &signature.optional_args {
             arg_strs.push(format!("[{}]", format_arg_spec(spec)));
         }
+
+        // Named args
+        for named in &signature.named_args {
+            match &named.default {
+                Some(default) => arg_strs.push(format!(
+                    "[{}={} (default {})]", named.name, format_arg_spec(&named.spec), format_value(default)
+                )),
+                None => arg_strs.push(format!("[{}={}]", named.name, format_arg_spec(&named.spec))),
+            }
+        }
         
         parts.push(arg_strs.join(", "));
         parts.push(")".to_string());
@@ -252,7 +577,42 @@ impl MethodRegistry {
         self.methods.keys().cloned().collect()
     }
 
-    fn validate_args(&self, signature: &MethodSignature, args: &[ASTNode]) -> Result<(), String> {
+    /// Checks every call in the chain is to a method that exists, before any
+    /// of them run. An unknown one is pointed at, with the closest known name.
+    pub fn check_methods(&self, ast: &ASTNode) -> Result<(), InterpretError> {
+        let calls = match ast {
+            ASTNode::MethodChain(calls) => calls.as_slice(),
+            call => std::slice::from_ref(call),
+        };
+
+        for call in calls {
+            if let ASTNode::MethodCall { name, span, .. } = call
+                && !self.methods.contains_key(name)
+            {
+                let mut error = InterpretError::new(format!("Unknown method: {}", name), *span);
+                error.suggestion = self.closest_method(name);
+                return Err(error);
+            }
+        }
+        Ok(())
+    }
+
+    // The known method nearest to a misspelt name, if any is near enough
+    fn closest_method(&self, name: &str) -> Option<String> {
+        // About one typo in three letters
+        let allowed = (name.chars().count() / 3).max(1);
+        let mut methods = self.list_methods();
+        methods.sort();
+        methods
+            .into_iter()
+            .map(|method| (edit_distance(name, &method), method))
+            .filter(|(distance, _)| *distance <= allowed)
+            .min_by_key(|(distance, _)| *distance)
+            .map(|(_, method)| method)
+    }
+
+    // Checks the arguments, returning the named ones with defaults filled in
+    fn validate_args(&self, signature: &MethodSignature, args: &[ASTNode], kwargs: &NamedArgs) -> Result<NamedArgs, String> {
         let min_args = signature.required_args.len();
         let max_args = min_args + signature.optional_args.len();
 
@@ -284,13 +644,31 @@ impl MethodRegistry {
                 }
             };
 
-            self.validate_arg(arg, spec, i, &signature.name)?;
+            self.validate_arg(arg, spec, &(i + 1).to_string(), &signature.name)?;
         }
 
-        Ok(())
+        // Then the named ones, which must be known to the method
+        for (key, value) in kwargs {
+            let Some(named) = signature.named_args.iter().find(|n| &n.name == key) else {
+                let known: Vec<&str> = signature.named_args.iter().map(|n| n.name.as_str()).collect();
+                return Err(match known.as_slice() {
+                    [] => format!("{}() takes no keyword arguments, got '{}'", signature.name, key),
+                    _ => format!("{}() has no argument '{}'. It takes: {}", signature.name, key, known.join(", ")),
+                });
+            };
+            self.validate_arg(value, &named.spec, &format!("'{}'", key), &signature.name)?;
+        }
+
+        let mut named = kwargs.clone();
+        for arg in &signature.named_args {
+            if let Some(default) = &arg.default {
+                named.entry(arg.name.clone()).or_insert_with(|| default.clone());
+            }
+        }
+        Ok(named)
     }
 
-    fn validate_arg(&self, arg: &ASTNode, spec: &ArgSpec, position: usize, method_name: &str) -> Result<(), String> {
+    fn validate_arg(&self, arg: &ASTNode, spec: &ArgSpec, which: &str, method_name: &str) -> Result<(), String> {
         match spec {
             ArgSpec::Identifier(valid_ids) => {
                 match arg {
@@ -299,7 +677,7 @@ impl MethodRegistry {
                             return Err(format!(
                                 "{}() argument {} must be one of: {}. Got '{}'",
                                 method_name,
-                                position + 1,
+                                which,
                                 valid_ids.join(", "),
                                 id
                             ));
@@ -309,7 +687,7 @@ impl MethodRegistry {
                     _ => Err(format!(
                         "{}() argument {} must be an identifier (one of: {})",
                         method_name,
-                        position + 1,
+                        which,
                         valid_ids.join(", ")
                     )),
                 }
@@ -320,15 +698,33 @@ impl MethodRegistry {
                     _ => Err(format!(
                         "{}() argument {} must be a quoted string literal",
                         method_name,
-                        position + 1
+                        which
                     )),
                 }
             }
+            ArgSpec::Integer => {
+                match arg {
+                    ASTNode::Integer(_) => Ok(()),
+                    _ => Err(format!("{}() argument {} must be a number", method_name, which)),
+                }
+            }
+            ArgSpec::Boolean => {
+                match arg {
+                    ASTNode::Boolean(_) => Ok(()),
+                    _ => Err(format!("{}() argument {} must be true or false", method_name, which)),
+                }
+            }
+            ArgSpec::Date => {
+                match arg {
+                    ASTNode::Date(_) => Ok(()),
+                    _ => Err(format!("{}() argument {} must be a date like 2026-11-01", method_name, which)),
+                }
+            }
             ArgSpec::Either(specs) => {
                 // Try each spec until one succeeds
                 let mut errors = Vec::new();
                 for spec in specs {
-                    match self.validate_arg(arg, spec, position, method_name) {
+                    match self.validate_arg(arg, spec, which, method_name) {
                         Ok(()) => return Ok(()),  // First match wins!
                         Err(e) => errors.push(e),
                     }
@@ -337,11 +733,10 @@ impl MethodRegistry {
                 Err(format!(
                     "{}() argument {} must match one of: {}",
                     method_name,
-                    position + 1,
-                    specs.iter().map(|s| format_arg_spec(s)).collect::<Vec<_>>().join(" OR ")
+                    which,
+                    specs.iter().map(format_arg_spec).collect::<Vec<_>>().join(" OR ")
                 ))
             }
-            ArgSpec::Any => Ok(()),
         }
     }
 }
@@ -352,13 +747,45 @@ fn format_arg_spec(spec: &ArgSpec) -> String {
     match spec {
         ArgSpec::Identifier(ids) => ids.join("|"),
         ArgSpec::Literal => "\"string\"".to_string(),
+        ArgSpec::Integer => "number".to_string(),
+        ArgSpec::Boolean => "true|false".to_string(),
+        ArgSpec::Date => "YYYY-MM-DD".to_string(),
         ArgSpec::Either(specs) => {
             let parts: Vec<String> = specs.iter()
-                .map(|s| format_arg_spec(s))
+                .map(format_arg_spec)
                 .collect();
             format!("({})", parts.join("|"))
         }
-        ArgSpec::Any => "any".to_string(),
+    }
+}
+
+// Levenshtein distance: the fewest single-character inserts, deletes and
+// substitutions that turn one string into the other
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+
+    for (i, ca) in a.chars().enumerate() {
+        let mut current = vec![i + 1];
+        for (j, cb) in b.iter().enumerate() {
+            let substitute = previous[j] + usize::from(ca != *cb);
+            current.push(substitute.min(previous[j + 1] + 1).min(current[j] + 1));
+        }
+        previous = current;
+    }
+    previous[b.len()]
+}
+
+// A value as it would be typed
+fn format_value(value: &ASTNode) -> String {
+    match value {
+        ASTNode::Literal(s) => format!("{:?}", s),
+        ASTNode::Identifier(s) => s.clone(),
+        ASTNode::Integer(n) => n.to_string(),
+        ASTNode::Boolean(b) => b.to_string(),
+        ASTNode::Date(date) => crate::timestamp::timestamp_to_string(*date),
+        ASTNode::MethodCall { name, .. } => format!("{}()", name),
+        ASTNode::MethodChain(_) => "...".to_string(),
     }
 }
 
@@ -397,58 +824,243 @@ fn parse_type_and_name(args: &[ASTNode]) -> Result<(String, String), String> {
 }
 
 
+// === Handler for edits: just the new value (applies to the current selection),
+// or type, name and new value
+fn parse_edit(args: &[ASTNode]) -> Result<(Option<(String, String)>, String), String> {
+    match args {
+        [ASTNode::Literal(value)] => Ok((None, value.clone())),
+        [ASTNode::Identifier(note_type), ASTNode::Literal(name), ASTNode::Literal(value)] => {
+            Ok((Some((note_type.clone(), name.clone())), value.clone()))
+        }
+        _ => Err("Expected a new value, or a type, a name and a new value".to_string()),
+    }
+}
+
+
 // ===== Method calls =====
 // Now each method just parses then does its thing
-fn method_new(args: &[ASTNode]) -> Result<(), String> {
+fn method_new(args: &[ASTNode], named: &NamedArgs, context: &mut Context, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
     let (note_type, name) = parse_type_and_name(args)?;
-    operations::new_method(&note_type, &name);
+    let due = match named.get("due") {
+        Some(ASTNode::Date(date)) => Some(*date),
+        _ => None,
+    };
+    let select = !matches!(named.get("select"), Some(ASTNode::Boolean(false)));
+    let id = operations::new_method(&note_type, &name, due, select, state, store)?;
+    context.subject = NodeKind::from_name(&note_type).map(|kind| (kind, id));
     Ok(())
 }
 
-fn method_delete(args: &[ASTNode]) -> Result<(), String> {
+fn method_delete(args: &[ASTNode], _named: &NamedArgs, context: &mut Context, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    // A trailing `force` allows deleting a record that still has children
+    let (args, force) = match args.split_last() {
+        Some((ASTNode::Identifier(id), rest)) if id == "force" => (rest, true),
+        _ => (args, false),
+    };
     let (note_type, name) = parse_type_and_name(args)?;
-    println!("Deleting {} with name: {}", note_type, name);
-    // Future: operations::delete_note(&note_type, &name)?;
+    operations::delete_method(&note_type, &name, force, state, store)?;
+    context.subject = None; // It may have been what was deleted
     Ok(())
 }
 
-fn show_project(args: &[ASTNode]) -> Result<(), String> {
+fn method_restore(args: &[ASTNode], _named: &NamedArgs, _context: &mut Context, _state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    match args {
+        // A bare name matches whatever kind was deleted under it
+        [ASTNode::Literal(name)] => operations::restore_method(None, name, store),
+        _ => {
+            let (note_type, name) = parse_type_and_name(args)?;
+            operations::restore_method(Some(&note_type), &name, store)
+        }
+    }
+}
+
+fn show_project(args: &[ASTNode], _named: &NamedArgs, _context: &mut Context, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
     // Validation already done by registry
-    if let ASTNode::Literal(name) = &args[0] {
-        
-        println!("Showing project with name: {}", name);
-        // Goes to operations to fetch and display
-        Ok(())
-    } else {
-        Err("show_project() expects a string literal".to_string())
+    match args.first() {
+        Some(ASTNode::Literal(name)) => operations::show_project_method(name, state, store),
+        None => operations::show_current_project(state, store),
+        _ => Err("show_project() expects a string literal".to_string()),
+    }
+}
+
+fn method_list(args: &[ASTNode], _named: &NamedArgs, _context: &mut Context, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    match args {
+        // Nothing named: the current project's children
+        [] => operations::list_children("project", None, state, store),
+        // Just a type: everything of that type in the current selection
+        [ASTNode::Identifier(note_type)] => operations::list_kind(note_type, state, store),
+        _ => {
+            let (note_type, name) = parse_type_and_name(args)?;
+            operations::list_children(&note_type, Some(&name), state, store)
+        }
+    }
+}
+fn method_title(args: &[ASTNode], _named: &NamedArgs, context: &mut Context, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    let (target, title) = parse_edit(args)?;
+    context.subject = Some(operations::title_method(edit_target(&target, context), &title, state, store)?);
+    Ok(())
+}
+
+fn method_describe(args: &[ASTNode], _named: &NamedArgs, context: &mut Context, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    let (target, description) = parse_edit(args)?;
+    context.subject = Some(operations::describe_method(edit_target(&target, context), &description, state, store)?);
+    Ok(())
+}
+
+fn method_primary(args: &[ASTNode], _named: &NamedArgs, _context: &mut Context, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    match args.first() {
+        None => operations::show_primary(state, store),
+        Some(ASTNode::Literal(name)) => operations::set_primary(Some(name), state, store),
+        Some(_) => operations::set_primary(None, state, store),
+    }
+}
+
+// === Handler for a record given by ID or by name
+fn parse_record_ref(arg: &ASTNode) -> Result<RecordRef<'_>, String> {
+    match arg {
+        ASTNode::Integer(id) => usize::try_from(*id)
+            .map(RecordRef::Id)
+            .map_err(|_| format!("{} isn't an ID", id)),
+        ASTNode::Literal(name) => Ok(RecordRef::Name(name)),
+        _ => Err("Expected an ID or a quoted name".to_string()),
+    }
+}
+
+// === The record a call is about when it doesn't name one: the chain's subject
+fn subject_ref(context: &Context, kind: NodeKind, method: &str) -> Result<RecordRef<'static>, String> {
+    match context.subject {
+        Some((subject_kind, id)) if subject_kind == kind => Ok(RecordRef::Id(id)),
+        _ => Err(format!(
+            "{}() has no {} to work on: name one, or chain it after a call that makes or changes one",
+            method, kind.name()
+        )),
     }
 }
 
-fn method_list(args: &[ASTNode]) -> Result<(), String> {
+// === The record an edit names, else the chain's subject, else the current selection
+fn edit_target<'a>(target: &'a Option<(String, String)>, context: &Context) -> Target<'a> {
+    match (target, context.subject) {
+        (Some((note_type, name)), _) => Target::Named(note_type, name),
+        (None, Some((kind, id))) => Target::Record(kind, id),
+        (None, None) => Target::Current,
+    }
+}
+
+// ===== Progress =====
+fn method_done(args: &[ASTNode], _named: &NamedArgs, context: &mut Context, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    // The item named, else the chain's subject
+    let (item, rest) = match args.first() {
+        Some(ASTNode::Integer(_) | ASTNode::Literal(_)) => (parse_record_ref(&args[0])?, &args[1..]),
+        _ => (subject_ref(context, NodeKind::Item, "done")?, args),
+    };
+    let completed = !matches!(rest.first(), Some(ASTNode::Boolean(false)));
+    let id = operations::done_method(item, completed, state, store)?;
+    context.subject = Some((NodeKind::Item, id));
+    Ok(())
+}
+
+fn method_due(args: &[ASTNode], _named: &NamedArgs, context: &mut Context, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    // The milestone named, else the chain's subject
+    let (milestone, date) = match args {
+        [ASTNode::Date(date)] => (subject_ref(context, NodeKind::Milestone, "due")?, *date),
+        [milestone, ASTNode::Date(date)] => (parse_record_ref(milestone)?, *date),
+        _ => return Err("due() expects a date".to_string()),
+    };
+    let id = operations::due_method(milestone, date, state, store)?;
+    context.subject = Some((NodeKind::Milestone, id));
+    Ok(())
+}
+
+// ===== Navigation =====
+// Moving drops the chain's subject, so edits after it land on the new location
+fn method_use(args: &[ASTNode], _named: &NamedArgs, context: &mut Context, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
     let (note_type, name) = parse_type_and_name(args)?;
-    println!("Listing children of {} with name: {}", note_type, name);
-    // Future: operations::list_children(&note_type, &name)?;
+    operations::use_method(&note_type, &name, state, store)?;
+    context.subject = None;
     Ok(())
 }
-fn method_title(args: &[ASTNode]) -> Result<(), String> {
-    // Validation already done by registry
-    if let ASTNode::Literal(title) = &args[0] {
-        
-        println!("Set title to: something");
+
+fn method_cd(args: &[ASTNode], _named: &NamedArgs, context: &mut Context, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    if let ASTNode::Literal(name) = &args[0] {
+        operations::cd_method(name, state, store)?;
+        context.subject = None;
         Ok(())
     } else {
-        Err("title() expects a string literal".to_string())
+        Err("cd() expects a string literal".to_string())
+    }
+}
+
+fn method_up(_args: &[ASTNode], _named: &NamedArgs, context: &mut Context, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    operations::up_method(state, store)?;
+    context.subject = None;
+    Ok(())
+}
+
+fn method_root(_args: &[ASTNode], _named: &NamedArgs, context: &mut Context, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    operations::root_method(state, store)?;
+    context.subject = None;
+    Ok(())
+}
+
+fn method_where(_args: &[ASTNode], _named: &NamedArgs, _context: &mut Context, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    operations::where_method(state, store)
+}
+
+fn method_back(_args: &[ASTNode], _named: &NamedArgs, context: &mut Context, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    operations::back_method(state, store)?;
+    context.subject = None;
+    Ok(())
+}
+
+fn method_forward(_args: &[ASTNode], _named: &NamedArgs, context: &mut Context, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    operations::forward_method(state, store)?;
+    context.subject = None;
+    Ok(())
+}
+
+fn method_history(_args: &[ASTNode], _named: &NamedArgs, _context: &mut Context, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    operations::history_method(state, store)
+}
+
+// ===== Maintenance =====
+fn method_migrate(args: &[ASTNode], _named: &NamedArgs, _context: &mut Context, _state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    operations::migrate_method(!args.is_empty(), store)
+}
+
+fn method_check(_args: &[ASTNode], _named: &NamedArgs, _context: &mut Context, _state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    operations::check_method(store)
+}
+
+// ===== Export and import =====
+fn method_export(args: &[ASTNode], _named: &NamedArgs, _context: &mut Context, _state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    match args {
+        [ASTNode::Identifier(format), ASTNode::Literal(path)] => {
+            operations::export_method(format, None, path, store)
+        }
+        [ASTNode::Identifier(format), ASTNode::Literal(project), ASTNode::Literal(path)] => {
+            operations::export_method(format, Some(project), path, store)
+        }
+        _ => Err("export() expects a format, maybe a project, and a file name".to_string()),
+    }
+}
+
+fn method_import(args: &[ASTNode], _named: &NamedArgs, _context: &mut Context, _state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    match args {
+        [ASTNode::Identifier(format), ASTNode::Literal(path)] => operations::import_method(format, path, store),
+        _ => Err("import() expects a format and a file name".to_string()),
     }
 }
 
 
 // ============ Main Handler ============
 
-pub fn handle_input(ast: ASTNode, registry: &MethodRegistry) {
+pub fn handle_input(ast: ASTNode, registry: &MethodRegistry, state: &mut FunkState, store: &mut dyn Store) {
     match ast {
         ASTNode::MethodChain(calls) => {
+            // Each call picks up from the one before
+            let mut context = Context::default();
             for call in calls {
-                if let Err(e) = execute_method(call, registry) {
+                if let Err(e) = execute_method(call, registry, &mut context, state, store) {
                     println!("Error: {}", e);
                     break;
                 }
@@ -458,10 +1070,10 @@ pub fn handle_input(ast: ASTNode, registry: &MethodRegistry) {
     }
 }
 
-fn execute_method(node: ASTNode, registry: &MethodRegistry) -> Result<(), String> {
+fn execute_method(node: ASTNode, registry: &MethodRegistry, context: &mut Context, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
     match node {
-        ASTNode::MethodCall { name, args } => {
-            registry.execute(&name, &args)
+        ASTNode::MethodCall { name, args, kwargs, .. } => {
+            registry.execute(&name, &args, &kwargs, context, state, store)
         }
         _ => Err("Expected MethodCall".to_string()),
     }
@@ -473,26 +1085,174 @@ fn execute_method(node: ASTNode, registry: &MethodRegistry) -> Result<(), String
 mod tests {
     use super::*;
     use crate::interpret::interpret;
+    use crate::store::MemoryStore;
 
-    #[test]
-    fn test_show_project_with_literal() {
-        let registry = MethodRegistry::new();
-        let input = "show_project(\"Test Project\")";
+    // Runs every call in the input, returning the last result
+    fn run(input: &str, registry: &MethodRegistry, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
         let ast = interpret(input).expect("Failed to parse input");
-        // This should not panic or return error
-        let result = match ast {
-            ASTNode::MethodCall { name, args } => registry.execute(&name, &args),
+        let mut context = Context::default();
+        match ast {
+            ASTNode::MethodCall { name, args, kwargs, .. } => registry.execute(&name, &args, &kwargs, &mut context, state, store),
             ASTNode::MethodChain(calls) => {
                 let mut last = Ok(());
                 for call in calls {
-                    if let ASTNode::MethodCall { name, args } = call {
-                        last = registry.execute(&name, &args);
+                    if let ASTNode::MethodCall { name, args, kwargs, .. } = call {
+                        last = registry.execute(&name, &args, &kwargs, &mut context, state, store);
                     }
                 }
                 last
             }
             _ => Err("Unexpected AST".to_string()),
-        };
-        assert!(result.is_ok(), "show_project failed: {:?}", result);
+        }
+    }
+
+    #[test]
+    fn test_show_project_with_literal() {
+        let registry = MethodRegistry::new();
+        let mut state = FunkState::default();
+        let mut store = MemoryStore::default();
+        let input = "show_project(\"Test Project\")";
+
+        // The literal passes validation and is looked up by name
+        let result = run(input, &registry, &mut state, &mut store);
+        assert_eq!(result, Err("No project named 'Test Project'".to_string()));
+
+        run("new(\"Test Project\")", &registry, &mut state, &mut store).unwrap();
+        assert_eq!(run(input, &registry, &mut state, &mut store), Ok(()));
+    }
+
+    #[test]
+    fn test_new_builds_on_the_current_selection() {
+        let registry = MethodRegistry::new();
+        let mut state = FunkState::default();
+        let mut store = MemoryStore::default();
+
+        let input = ".new(\"Garden\").new(object, \"Beds\").title(\"Raised beds\").new(item, \"Buy soil\")";
+        handle_input(interpret(input).unwrap(), &registry, &mut state, &mut store);
+
+        let notebook = &store.notebook;
+        assert_eq!(notebook.notes[0].objects, vec![2]);
+        assert_eq!(notebook.objects[0].title, "Raised beds");
+        assert_eq!(notebook.objects[0].items, vec![3]);
+        assert_eq!(notebook.items[0].text, "Buy soil");
+        assert_eq!(store.last_id, 3);
+        assert_eq!(state.current_object_id, Some(2));
+    }
+
+    #[test]
+    fn test_chained_calls_work_on_the_last_record() {
+        let registry = MethodRegistry::new();
+        let mut state = FunkState::default();
+        let mut store = MemoryStore::default();
+
+        let input = r#".new("Garden").new(object, "API").new(item, "write docs").done().title("Write the docs")
+            .new(milestone, "Beta").due(2026-12-01).describe("First outside users")"#;
+        handle_input(interpret(input).unwrap(), &registry, &mut state, &mut store);
+
+        let notebook = &store.notebook;
+        assert_eq!(notebook.objects[0].title, "API");
+        assert_eq!(notebook.items[0].text, "Write the docs");
+        assert!(notebook.items[0].completed);
+        assert_eq!(notebook.milestones[0].target_date, 1796083200);
+        assert_eq!(notebook.milestones[0].description, "First outside users");
+
+        // Each chain starts with no subject, so this renames the current object
+        run(r#"title("Backend")"#, &registry, &mut state, &mut store).unwrap();
+        assert_eq!(store.notebook.objects[0].title, "Backend");
+
+        // Nor does a subject of the wrong kind count
+        let error = run(r#".new(item, "Test").due(2027-01-01)"#, &registry, &mut state, &mut store).unwrap_err();
+        assert_eq!(error, "due() has no milestone to work on: name one, or chain it after a call that makes or changes one");
+    }
+
+    #[test]
+    fn test_moving_drops_the_subject() {
+        let registry = MethodRegistry::new();
+        let mut state = FunkState::default();
+        let mut store = MemoryStore::default();
+
+        // After cd the edit is for the project moved to, not the object just made
+        run(r#".new("Garden").new(object, "A").cd("..").title("X")"#, &registry, &mut state, &mut store).unwrap();
+        assert_eq!(store.notebook.notes[0].title, "X");
+        assert_eq!(store.notebook.objects[0].title, "A");
+
+        // Likewise after use(), up() and root()
+        run(r#".cd("A").new(item, "Weed").use(object, "A").title("Beds")"#, &registry, &mut state, &mut store).unwrap();
+        assert_eq!(store.notebook.objects[0].title, "Beds");
+        assert_eq!(store.notebook.items[0].text, "Weed");
+        run(r#".new(object, "Shed").up().describe("Veggie patch")"#, &registry, &mut state, &mut store).unwrap();
+        assert_eq!(store.notebook.notes[0].description, "Veggie patch");
+        assert_eq!(store.notebook.objects[1].description, "");
+        let error = run(r#".new(object, "Path").root().title("Y")"#, &registry, &mut state, &mut store).unwrap_err();
+        assert!(error.starts_with("No project selected"), "{}", error);
+        assert_eq!(store.notebook.objects[2].title, "Path");
+    }
+
+    #[test]
+    fn test_ids_dates_and_booleans() {
+        let registry = MethodRegistry::new();
+        let mut state = FunkState::default();
+        let mut store = MemoryStore::default();
+        run(".new(\"Garden\").new(object, \"Beds\").new(item, \"Buy soil\").new(milestone, \"Harvest\")",
+            &registry, &mut state, &mut store).unwrap();
+
+        run("done(3)", &registry, &mut state, &mut store).unwrap();
+        assert!(store.notebook.items[0].completed && store.notebook.items[0].completed_on.is_some());
+        run("done(\"Buy soil\", false)", &registry, &mut state, &mut store).unwrap();
+        assert_eq!(store.notebook.items[0].completed_on, None);
+
+        run("due(\"Harvest\", 2026-11-01)", &registry, &mut state, &mut store).unwrap();
+        assert_eq!(store.notebook.milestones[0].target_date, 1793491200);
+
+        let mut err = |input| run(input, &registry, &mut state, &mut store).unwrap_err();
+        assert_eq!(err("due(4, \"soon\")"), "due() argument 2 must be a date like 2026-11-01");
+        assert_eq!(err("done(3, yes)"), "done() argument 2 must be true or false");
+        assert_eq!(err("done(2026-11-01)"), "done() argument 1 must match one of: number OR \"string\" OR true|false");
+        assert_eq!(err("done(true)"), "done() has no item to work on: name one, or chain it after a call that makes or changes one");
+        assert_eq!(err("done(-3)"), "-3 isn't an ID");
+        assert_eq!(err("done(99)"), "item 99 doesn't exist");
+    }
+
+    #[test]
+    fn test_keyword_arguments_and_defaults() {
+        let registry = MethodRegistry::new();
+        let mut state = FunkState::default();
+        let mut store = MemoryStore::default();
+
+        run(r#".new("Garden").new(object, "Beds").new(object, "Shed", select = false)"#, &registry, &mut state, &mut store).unwrap();
+        assert_eq!(state.current_object_id, Some(2)); // Still in Beds
+        run(r#"new(milestone, "Harvest", due = 2026-11-01)"#, &registry, &mut state, &mut store).unwrap();
+        assert_eq!(store.notebook.milestones[0].target_date, 1793491200);
+
+        let signature = &registry.methods["new"];
+        let named = registry.validate_args(signature, &[ASTNode::Literal("X".to_string())], &NamedArgs::new()).unwrap();
+        assert_eq!(named, NamedArgs::from([("select".to_string(), ASTNode::Boolean(true))]));
+        assert!(registry.format_signature(signature).ends_with("[due=YYYY-MM-DD], [select=true|false (default true)])"));
+
+        let mut err = |input| run(input, &registry, &mut state, &mut store).unwrap_err();
+        assert_eq!(err(r#"new("X", colour = "red")"#), "new() has no argument 'colour'. It takes: due, select");
+        assert_eq!(err(r#"new("X", select = "no")"#), "new() argument 'select' must be true or false");
+        assert_eq!(err(r#"up(fast = true)"#), "up() takes no keyword arguments, got 'fast'");
+        assert_eq!(err(r#"new(item, "X", due = 2026-11-01)"#), "Only milestones have a due date");
+        assert_eq!(store.notebook.objects.len(), 2);
+    }
+
+    #[test]
+    fn test_unknown_methods_get_suggestions() {
+        let registry = MethodRegistry::new();
+        let check = |input| registry.check_methods(&interpret(input).unwrap());
+
+        assert_eq!(check(r#".new("Garden").list()"#), Ok(()));
+        let error = check(r#".new("Garden").lst()"#).unwrap_err();
+        assert_eq!(error.message, "Unknown method: lst");
+        assert_eq!(error.suggestion, Some("list".to_string()));
+        assert_eq!(error.render(r#".new("Garden").lst()"#), "Unknown method: lst\n  .new(\"Garden\").lst()\n                 ^^^ did you mean `list`?");
+
+        assert_eq!(check("descibe()").unwrap_err().suggestion, Some("describe".to_string()));
+        assert_eq!(check("frobnicate()").unwrap_err().suggestion, None);
+
+        assert_eq!(edit_distance("kitten", "sitting"), 3);
+        assert_eq!(edit_distance("", "up"), 2);
+        assert_eq!(edit_distance("use", "use"), 0);
     }
 }
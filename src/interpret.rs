@@ -1,10 +1,19 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::timestamp::string_to_timestamp;
+
 // Public API types
 #[derive(Debug, PartialEq, Clone)]
 pub enum ASTNode {
-    MethodCall { name: String, args: Vec<ASTNode> },
+    // span covers the method's name
+    MethodCall { name: String, args: Vec<ASTNode>, kwargs: BTreeMap<String, ASTNode>, span: Span },
     MethodChain(Vec<ASTNode>),
     Literal(String),
     Identifier(String),
+    Integer(i64),
+    Boolean(bool),
+    Date(u64),          // Midnight UTC, as a unix timestamp
 }
 
 #[derive(Debug, PartialEq, Clone)]
@@ -14,79 +23,212 @@ pub enum Token {
     LeftParen,
     RightParen,
     StringLiteral(String),
+    Integer(i64),
+    Boolean(bool),
+    Date(u64),
     Comma,
+    Equals,
+}
+
+/// Where something is in the input, as a range of byte offsets.
+#[derive(Debug, PartialEq, Clone, Copy, Default)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize,
+}
+
+impl Span {
+    pub fn new(start: usize, end: usize) -> Span {
+        Span { start, end }
+    }
+}
+
+/// Why the input couldn't be read, and which part of it is wrong.
+#[derive(Debug, PartialEq, Clone)]
+pub struct InterpretError {
+    pub message: String,
+    pub span: Span,
+    pub suggestion: Option<String>,   // What was probably meant
 }
 
+impl InterpretError {
+    pub fn new(message: impl Into<String>, span: Span) -> InterpretError {
+        InterpretError { message: message.into(), span, suggestion: None }
+    }
+
+    /// The message, then the line of input it's about with the span underlined:
+    ///
+    ///   Unknown method: lst
+    ///     .new("Garden").lst()
+    ///                    ^^^ did you mean `list`?
+    pub fn render(&self, input: &str) -> String {
+        let start = self.span.start.min(input.len());
+        let line_start = input[..start].rfind('\n').map_or(0, |i| i + 1);
+        let line_end = input[start..].find('\n').map_or(input.len(), |i| start + i);
+        let end = self.span.end.clamp(start, line_end);
+
+        let pad = " ".repeat(input[line_start..start].chars().count());
+        let carets = "^".repeat(input[start..end].chars().count().max(1));
+        let hint = match &self.suggestion {
+            Some(suggestion) => format!(" did you mean `{}`?", suggestion),
+            None => String::new(),
+        };
+        format!("{}\n  {}\n  {}{}{}", self.message, &input[line_start..line_end], pad, carets, hint)
+    }
+}
+
+impl fmt::Display for InterpretError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+type Result<T> = std::result::Result<T, InterpretError>;
+
 const SKIP_EXP: &[&str] = &["funknotes", "funk"];
 
+// The input with each character's byte offset
+type Chars<'a> = std::iter::Peekable<std::str::CharIndices<'a>>;
+
 // Main entry point
-pub fn interpret(input: &str) -> Result<ASTNode, String> {
+pub fn interpret(input: &str) -> Result<ASTNode> {
     let tokens = tokenize(input)?;
-    let mut parser = Parser::new(tokens);
+    let mut parser = Parser::new(tokens, input.len());
     parser.parse()
 }
 
 // Lexer
-fn tokenize(input: &str) -> Result<Vec<Token>, String> {
+fn tokenize(input: &str) -> Result<Vec<(Token, Span)>> {
     let mut tokens = Vec::new();
-    let mut chars = input.chars().peekable();
+    let mut chars = input.char_indices().peekable();
 
-    while let Some(&ch) = chars.peek() {
-        match ch {
+    while let Some(&(at, ch)) = chars.peek() {
+        let token = match ch {
             ' ' | '\t' | '\n' => {
                 chars.next();
+                continue;
             }
             '.' => {
-                tokens.push(Token::Dot);
                 chars.next();
+                Token::Dot
             }
             '(' => {
-                tokens.push(Token::LeftParen);
                 chars.next();
+                Token::LeftParen
             }
             ')' => {
-                tokens.push(Token::RightParen);
                 chars.next();
+                Token::RightParen
             }
-            '"' => {
+            '"' | '\'' => {
                 chars.next();
-                let string = consume_string(&mut chars)?;
-                tokens.push(Token::StringLiteral(string));
+                Token::StringLiteral(consume_string(&mut chars, input, at, ch)?)
             }
             'a'..='z' | 'A'..='Z' | '_' => {
                 let ident = consume_identifier(&mut chars);
-                tokens.push(Token::Identifier(ident));
+                match ident.as_str() {
+                    "true" => Token::Boolean(true),
+                    "false" => Token::Boolean(false),
+                    _ => Token::Identifier(ident),
+                }
             }
+            '0'..='9' | '-' => consume_number(&mut chars, input, at)?,
             ',' => {
-                tokens.push(Token::Comma);
                 chars.next();
+                Token::Comma
             }
-            _ => return Err(format!("Unexpected character: {}", ch)),
-        }
+            '=' => {
+                chars.next();
+                Token::Equals
+            }
+            _ => return Err(InterpretError::new(format!("Unexpected character: {}", ch), Span::new(at, at + ch.len_utf8()))),
+        };
+        tokens.push((token, Span::new(at, offset(&mut chars, input))));
     }
 
     Ok(tokens)
 }
 
-fn consume_string(chars: &mut std::iter::Peekable<std::str::Chars>) -> Result<String, String> {
+// The byte offset of the next character
+fn offset(chars: &mut Chars, input: &str) -> usize {
+    chars.peek().map_or(input.len(), |&(at, _)| at)
+}
+
+// A string after its opening quote, at byte `start`. Double-quoted strings take
+// escapes; single-quoted ones are raw, running to the next single quote.
+fn consume_string(chars: &mut Chars, input: &str, start: usize, quote: char) -> Result<String> {
     let mut string = String::new();
 
-    while let Some(&ch) = chars.peek() {
-        if ch == '"' {
-            chars.next();
-            return Ok(string);
+    while let Some((at, ch)) = chars.next() {
+        match ch {
+            _ if ch == quote => return Ok(string),
+            '\\' if quote == '"' => string.push(consume_escape(chars, input, at)?),
+            _ => string.push(ch),
         }
-        string.push(ch);
-        chars.next();
     }
 
-    Err("Unclosed string literal".to_string())
+    Err(InterpretError::new("Unclosed string literal", Span::new(start, input.len())))
+}
+
+// The character for an escape whose backslash is at byte `start`
+fn consume_escape(chars: &mut Chars, input: &str, start: usize) -> Result<char> {
+    let Some((_, ch)) = chars.next() else {
+        return Err(InterpretError::new("Unclosed string literal: nothing after the escape", Span::new(start, input.len())));
+    };
+
+    match ch {
+        '"' => Ok('"'),
+        '\\' => Ok('\\'),
+        'n' => Ok('\n'),
+        't' => Ok('\t'),
+        'u' => {
+            // \u{...}: one to six hex digits
+            let opened = chars.next_if(|&(_, c)| c == '{').is_some();
+            let mut hex = String::new();
+            while let Some((_, c)) = chars.next_if(|&(_, c)| c.is_ascii_hexdigit()) {
+                hex.push(c);
+            }
+            let closed = opened && chars.next_if(|&(_, c)| c == '}').is_some();
+
+            let span = Span::new(start, offset(chars, input));
+            if !closed || hex.is_empty() || hex.len() > 6 {
+                return Err(InterpretError::new("Malformed \\u escape: expected \\u{hex}", span));
+            }
+            let code = u32::from_str_radix(&hex, 16).expect("hex digits");
+            char::from_u32(code).ok_or_else(|| InterpretError::new(format!("\\u{{{}}} isn't a character", hex), span))
+        }
+        _ => Err(InterpretError::new(
+            format!("Unknown escape '\\{}'", ch),
+            Span::new(start, start + 1 + ch.len_utf8()),
+        )),
+    }
 }
 
-fn consume_identifier(chars: &mut std::iter::Peekable<std::str::Chars>) -> String {
+// An integer, or a date when there are dashes after the first digit
+fn consume_number(chars: &mut Chars, input: &str, start: usize) -> Result<Token> {
+    let mut number = String::new();
+
+    while let Some((_, ch)) = chars.next_if(|&(_, ch)| ch.is_ascii_digit() || ch == '-') {
+        number.push(ch);
+    }
+    let span = Span::new(start, offset(chars, input));
+
+    if number[1..].contains('-') {
+        return string_to_timestamp(&number)
+            .map(Token::Date)
+            .ok_or_else(|| InterpretError::new(format!("Invalid date: {} (expected YYYY-MM-DD)", number), span));
+    }
+    match number.parse::<i64>() {
+        Ok(n) => Ok(Token::Integer(n)),
+        Err(_) if number == "-" => Err(InterpretError::new("Unexpected character: -", span)),
+        Err(_) => Err(InterpretError::new(format!("Number out of range: {}", number), span)),
+    }
+}
+
+fn consume_identifier(chars: &mut Chars) -> String {
     let mut ident = String::new();
 
-    while let Some(&ch) = chars.peek() {
+    while let Some(&(_, ch)) = chars.peek() {
         if ch.is_alphanumeric() || ch == '_' {
             ident.push(ch);
             chars.next();
@@ -100,31 +242,41 @@ fn consume_identifier(chars: &mut std::iter::Peekable<std::str::Chars>) -> Strin
 
 // Parser
 struct Parser {
-    tokens: Vec<Token>,
+    tokens: Vec<(Token, Span)>,
     position: usize,
+    end: usize,     // Length of the input, for errors at the end of it
 }
 
 impl Parser {
-    fn new(tokens: Vec<Token>) -> Self {
-        Self { tokens, position: 0 }
+    fn new(tokens: Vec<(Token, Span)>, end: usize) -> Self {
+        Self { tokens, position: 0, end }
     }
 
     fn current(&self) -> Option<&Token> {
-        self.tokens.get(self.position)
+        self.tokens.get(self.position).map(|(token, _)| token)
+    }
+
+    // Where the current token is, or the end of the input after the last one
+    fn span(&self) -> Span {
+        self.tokens.get(self.position).map_or(Span::new(self.end, self.end), |&(_, span)| span)
+    }
+
+    fn error(&self, message: &str) -> InterpretError {
+        InterpretError::new(message, self.span())
     }
 
     fn advance(&mut self) {
         self.position += 1;
     }
 
-    fn parse(&mut self) -> Result<ASTNode, String> {
+    fn parse(&mut self) -> Result<ASTNode> {
         let mut calls = Vec::new();
 
         // Skip optional prefix
-        if let Some(Token::Identifier(name)) = self.current() {
-            if SKIP_EXP.contains(&name.as_str()) {
-                self.advance();
-            }
+        if let Some(Token::Identifier(name)) = self.current()
+            && SKIP_EXP.contains(&name.as_str())
+        {
+            self.advance();
         }
 
         // Parse method chain
@@ -134,7 +286,7 @@ impl Parser {
             } else if calls.is_empty() {
                 // First method doesn't need a dot
             } else {
-                return Err("Expected '.'".to_string());
+                return Err(self.error("Expected '.'"));
             }
 
             let method = self.parse_expression()?;
@@ -144,34 +296,43 @@ impl Parser {
         Ok(ASTNode::MethodChain(calls))
     }
 
-    fn parse_expression(&mut self) -> Result<ASTNode, String> {
+    fn parse_expression(&mut self) -> Result<ASTNode> {
         // Method name
+        let span = self.span();
         let name = match self.current() {
             Some(Token::Identifier(n)) => n.clone(),
-            _ => return Err("Expected method name".to_string()),
+            _ => return Err(self.error("Expected method name")),
         };
         self.advance();
 
         // Left paren
         match self.current() {
             Some(Token::LeftParen) => self.advance(),
-            _ => return Err("Expected '('".to_string()),
+            _ => return Err(self.error("Expected '('")),
         }
 
-        // Arguments
+        // Arguments: positional ones, then any `name = value` ones
         let mut args = Vec::new();
+        let mut kwargs = BTreeMap::new();
         loop {
-            match self.current() {
-                Some(Token::StringLiteral(s)) => {
-                    args.push(ASTNode::Literal(s.clone()));
-                    self.advance();
+            if let Some(Token::RightParen) = self.current() {
+                break;
+            }
+
+            if let (Some(Token::Identifier(key)), Some((Token::Equals, _))) = (self.current(), self.tokens.get(self.position + 1)) {
+                let (key, key_span) = (key.clone(), self.span());
+                self.position += 2;
+                let value = self.parse_value()?;
+                if kwargs.insert(key.clone(), value).is_some() {
+                    return Err(InterpretError::new(format!("Keyword argument '{}' given twice", key), key_span));
                 }
-                Some(Token::Identifier(s)) => {
-                    args.push(ASTNode::Identifier(s.clone()));
-                    self.advance();
+            } else {
+                let value_span = self.span();
+                let value = self.parse_value()?;
+                if !kwargs.is_empty() {
+                    return Err(InterpretError::new("Positional arguments must come before keyword arguments", value_span));
                 }
-                Some(Token::RightParen) => break,
-                _ => return Err("Expected string, identifier, or ')'".to_string()),
+                args.push(value);
             }
 
             if let Some(Token::Comma) = self.current() {
@@ -184,10 +345,24 @@ impl Parser {
         // Right paren
         match self.current() {
             Some(Token::RightParen) => self.advance(),
-            _ => return Err("Expected ')'".to_string()),
+            _ => return Err(self.error("Expected ')'")),
         }
 
-        Ok(ASTNode::MethodCall { name, args })
+        Ok(ASTNode::MethodCall { name, args, kwargs, span })
+    }
+
+    // One argument's value
+    fn parse_value(&mut self) -> Result<ASTNode> {
+        let value = match self.current() {
+            Some(Token::StringLiteral(s)) => ASTNode::Literal(s.clone()),
+            Some(Token::Identifier(s)) => ASTNode::Identifier(s.clone()),
+            Some(Token::Integer(n)) => ASTNode::Integer(*n),
+            Some(Token::Boolean(b)) => ASTNode::Boolean(*b),
+            Some(Token::Date(date)) => ASTNode::Date(*date),
+            _ => return Err(self.error("Expected a string, identifier, number, true/false, date or ')'")),
+        };
+        self.advance();
+        Ok(value)
     }
 }
 
@@ -196,9 +371,109 @@ impl Parser {
 mod tests {
     use super::*;
 
+    // The message and byte range of the error for some input
+    fn err(input: &str) -> (String, usize, usize) {
+        let error = interpret(input).unwrap_err();
+        (error.message, error.span.start, error.span.end)
+    }
+
+    fn call(name: &str, args: Vec<ASTNode>, span: Span) -> ASTNode {
+        ASTNode::MethodCall { name: name.to_string(), args, kwargs: BTreeMap::new(), span }
+    }
+
     #[test]
     fn test_basic_parse() {
         let result = interpret("create(\"note\").tag(\"important\")");
         assert!(result.is_ok());
     }
+
+    #[test]
+    fn test_numbers_booleans_and_dates() {
+        let f = |args| ASTNode::MethodChain(vec![call("f", args, Span::new(0, 1))]);
+
+        assert_eq!(interpret("f(3, -12, 0)"), Ok(f(vec![ASTNode::Integer(3), ASTNode::Integer(-12), ASTNode::Integer(0)])));
+        assert_eq!(interpret("f(true,false)"), Ok(f(vec![ASTNode::Boolean(true), ASTNode::Boolean(false)])));
+        assert_eq!(interpret("f(2026-11-01)"), Ok(f(vec![ASTNode::Date(1793491200)])));
+        // Identifiers may still have digits in them
+        assert_eq!(interpret("f(v2)"), Ok(f(vec![ASTNode::Identifier("v2".to_string())])));
+
+        assert_eq!(err("f(2026-02-30)"), ("Invalid date: 2026-02-30 (expected YYYY-MM-DD)".to_string(), 2, 12));
+        assert_eq!(err("f(2026-1-1)"), ("Invalid date: 2026-1-1 (expected YYYY-MM-DD)".to_string(), 2, 10));
+        assert_eq!(err("f(99999999999999999999)"), ("Number out of range: 99999999999999999999".to_string(), 2, 22));
+        assert_eq!(err("f(-)"), ("Unexpected character: -".to_string(), 2, 3));
+    }
+
+    #[test]
+    fn test_string_escapes_and_raw_strings() {
+        let literal = |input| match interpret(input) {
+            Ok(ASTNode::MethodChain(calls)) => match &calls[..] {
+                [ASTNode::MethodCall { args, .. }] => args.clone(),
+                _ => panic!("one call expected"),
+            },
+            other => panic!("{:?}", other),
+        };
+
+        assert_eq!(literal(r#"f("say \"hi\"\n\tback\\slash")"#), vec![ASTNode::Literal("say \"hi\"\n\tback\\slash".to_string())]);
+        assert_eq!(literal(r#"f("\u{e9}t\u{E9} \u{1F331}")"#), vec![ASTNode::Literal("été 🌱".to_string())]);
+        // Single quotes are raw: backslashes and double quotes stay as they are
+        assert_eq!(literal(r#"f('C:\notes\"new"', "")"#), vec![
+            ASTNode::Literal(r#"C:\notes\"new""#.to_string()),
+            ASTNode::Literal(String::new()),
+        ]);
+    }
+
+    #[test]
+    fn test_string_errors_have_positions() {
+        let malformed = "Malformed \\u escape: expected \\u{hex}".to_string();
+
+        assert_eq!(err(r#"new("Garden"#), ("Unclosed string literal".to_string(), 4, 11));
+        assert_eq!(err("new('Garden)"), ("Unclosed string literal".to_string(), 4, 12));
+        assert_eq!(err(r#"new("Gar\"#), ("Unclosed string literal: nothing after the escape".to_string(), 8, 9));
+        assert_eq!(err(r#"new("é\q")"#), ("Unknown escape '\\q'".to_string(), 7, 9));
+        assert_eq!(err(r#"new("\u00e9")"#), (malformed.clone(), 5, 11));
+        assert_eq!(err(r#"new("\u{}")"#), (malformed.clone(), 5, 9));
+        assert_eq!(err(r#"new("\u{1234567}")"#), (malformed.clone(), 5, 16));
+        assert_eq!(err(r#"new("\u{e9")"#), (malformed, 5, 10));
+        assert_eq!(err(r#"new("\u{D800}")"#), ("\\u{D800} isn't a character".to_string(), 5, 13));
+    }
+
+    #[test]
+    fn test_keyword_arguments() {
+        let parsed = interpret(r#"new(milestone, "Release", due = 2026-11-01, select=false)"#).unwrap();
+        let expected = ASTNode::MethodCall {
+            name: "new".to_string(),
+            args: vec![ASTNode::Identifier("milestone".to_string()), ASTNode::Literal("Release".to_string())],
+            kwargs: BTreeMap::from([
+                ("due".to_string(), ASTNode::Date(1793491200)),
+                ("select".to_string(), ASTNode::Boolean(false)),
+            ]),
+            span: Span::new(0, 3),
+        };
+        assert_eq!(parsed, ASTNode::MethodChain(vec![expected]));
+
+        assert_eq!(err("f(a = 1, a = 2)"), ("Keyword argument 'a' given twice".to_string(), 9, 10));
+        assert_eq!(err("f(a = 1, 2)"), ("Positional arguments must come before keyword arguments".to_string(), 9, 10));
+        assert_eq!(err("f(a = )"), ("Expected a string, identifier, number, true/false, date or ')'".to_string(), 6, 7));
+    }
+
+    #[test]
+    fn test_parse_errors_point_at_the_problem() {
+        assert_eq!(
+            interpret(r#"funk .new("A").title("B")"#),
+            Ok(ASTNode::MethodChain(vec![
+                call("new", vec![ASTNode::Literal("A".to_string())], Span::new(6, 9)),
+                call("title", vec![ASTNode::Literal("B".to_string())], Span::new(15, 20)),
+            ]))
+        );
+
+        assert_eq!(err(".new"), ("Expected '('".to_string(), 4, 4));
+        assert_eq!(err(r#"new("A") up()"#), ("Expected '.'".to_string(), 9, 11));
+        assert_eq!(err(r#"new("A" "B")"#), ("Expected ')'".to_string(), 8, 11));
+        assert_eq!(err("new(#)"), ("Unexpected character: #".to_string(), 4, 5));
+
+        let error = interpret(r#"new("é" "B")"#).unwrap_err();
+        assert_eq!(error.render(r#"new("é" "B")"#), "Expected ')'\n  new(\"é\" \"B\")\n          ^^^");
+        // At the end of the input there's nothing to underline, so a single caret goes after it
+        assert_eq!(interpret("up(").unwrap_err().render("up("), "Expected a string, identifier, number, true/false, date or ')'\n  up(\n     ^");
+    }
 }
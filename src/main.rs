@@ -1,39 +1,112 @@
+
 mod interpret;
 mod types;
 mod functions;
 mod timestamp;
 mod storage;
+mod store;
 mod operations;
+mod export;
+mod binary_store;
 
-use crate::functions::{handle_input, MethodRegistry};
-use crate::interpret::interpret;
+use crate::functions::MethodRegistry;
+use crate::interpret::{interpret, ASTNode, InterpretError};
+use crate::storage::Backend;
+use crate::store::Store;
 use crate::types::FunkState;
 use std::env;
 use std::io::{self, Write};
+use std::process;
 
 const EXIT_CMDS: &[&str] = &["exit", "quit", "q", "drop"];
 
 fn main() {
     let args: Vec<String> = env::args().collect();
-    let full_input = args[1..].join(" ");
+    let flags = take_flag(&args[1..], "--data-dir").and_then(|(data_dir, args)| {
+        let (store, args) = take_flag(&args, "--store")?;
+        let (convert, args) = take_flag(&args, "--convert")?;
+        Ok((data_dir, store, convert, args))
+    });
+    let (data_dir_flag, store_flag, convert_flag, args) = match flags {
+        Ok(found) => found,
+        Err(e) => {
+            println!("Error: {}", e);
+            process::exit(2);
+        }
+    };
+    let full_input = args.join(" ");
     
     let registry = MethodRegistry::new();
+    let opened = storage::data_dir(data_dir_flag.as_deref()).and_then(|dir| {
+        let mut backend = storage::backend(&dir, store_flag.as_deref())?;
+        if let Some(to) = &convert_flag {
+            let to = Backend::from_name(to).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+            storage::convert_store(&dir, backend, to)?;
+            println!("Converted the notes from the {} store to the {} store.", backend.name(), to.name());
+            backend = to;
+        }
+        storage::open_backend(&dir, backend)
+    });
+    let mut store = match opened {
+        Ok(store) => store,
+        Err(e) => {
+            println!("Error: couldn't open the data directory: {}", e);
+            process::exit(1);
+        }
+    };
+    // Pick up where the last run left off
+    let mut state = operations::load_state(&mut *store);
     
     if full_input.contains("shell()") || full_input == "shell" {
-        run_shell(registry);
+        run_shell(registry, &mut state, &mut *store);
     } else if !full_input.is_empty() {
-        match interpret(&full_input) {
-            Ok(ast) => functions::handle_input(ast, &registry),
-            Err(e) => println!("Error: {}", e),
+        match parse(&full_input, &registry) {
+            Ok(ast) => {
+                functions::handle_input(ast, &registry, &mut state, &mut *store);
+                operations::save_state(&mut state, &mut *store);
+            }
+            Err(e) => println!("Error: {}", e.render(&full_input)),
         }
-    } else {
+    } else if convert_flag.is_none() {
         println!("Usage:");
         println!("  funknotes shell              - Enter REPL");
         println!("  funknotes .new().title(\"X\")  - Single command");
+        println!();
+        println!("Notes are kept in --data-dir <dir>, else ${}, else $XDG_DATA_HOME/funknotes", storage::HOME_VAR);
+        println!("(~/.local/share/funknotes when XDG_DATA_HOME isn't set).");
+        println!("--store <text|binary> picks how they're kept (else ${}, else the last one", storage::STORE_VAR);
+        println!("converted to, else whichever is there); --convert <text|binary> copies them across.");
+    }
+}
+
+// Pulls `<flag> <value>` or `<flag>=<value>` out of the arguments,
+// leaving the rest to be read as a command
+fn take_flag(args: &[String], flag: &str) -> Result<(Option<String>, Vec<String>), String> {
+    let mut value = None;
+    let mut rest = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg == flag {
+            let found = args.next().ok_or(format!("{} needs a value", flag))?;
+            value = Some(found.clone());
+        } else if let Some(found) = arg.strip_prefix(flag).and_then(|a| a.strip_prefix('=')) {
+            value = Some(found.to_string());
+        } else {
+            rest.push(arg.clone());
+        }
     }
+    Ok((value, rest))
+}
+
+// Reads a command, checking the methods it calls exist before any of them run
+fn parse(input: &str, registry: &MethodRegistry) -> Result<ASTNode, InterpretError> {
+    let ast = interpret(input)?;
+    registry.check_methods(&ast)?;
+    Ok(ast)
 }
 
-fn run_shell(registry: MethodRegistry) {
+fn run_shell(registry: MethodRegistry, state: &mut FunkState, store: &mut dyn Store) {
     println!("Entering funk_notes shell.");
     println!("Available methods: {:?}", registry.list_methods());
     println!("Prefix with '?' to just parse without executing.\n");
@@ -57,15 +130,18 @@ fn run_shell(registry: MethodRegistry) {
         }
 
         if !input.is_empty() {
-            if input.starts_with('?') {
-                match interpret(&input[1..]) {
+            if let Some(query) = input.strip_prefix('?') {
+                match parse(query, &registry) {
                     Ok(ast) => println!("✓ {:#?}\n", ast),
-                    Err(e) => println!("✗ {}\n", e),
+                    Err(e) => println!("✗ {}\n", e.render(query)),
                 }
             } else {
-                match interpret(input) {
-                    Ok(ast) => functions::handle_input(ast, &registry),
-                    Err(e) => println!("✗ {}\n", e),
+                match parse(input, &registry) {
+                    Ok(ast) => {
+                        functions::handle_input(ast, &registry, state, store);
+                        operations::save_state(state, store);
+                    }
+                    Err(e) => println!("✗ {}\n", e.render(input)),
                 }
             }
         }
@@ -1,47 +1,1369 @@
-use std::fmt::Error;
-
-use crate::timestamp::now_timestamp;
+use crate::export;
+use crate::timestamp::{now_timestamp, timestamp_to_string};
 use crate::types::*;
-use crate::storage::*;
+use crate::store::{take_subtree, Store};
+
+// How many places back() can go
+const MAX_HISTORY: usize = 50;
+
+// Here we'll do all the actual function work with the types
+// New projects and objects become current, unless select is false
+// Returns the new record's ID
+pub fn new_method(target_object: &str, title: &str, due: Option<u64>, select: bool, state: &mut FunkState, store: &mut dyn Store) -> Result<usize, String> {
+    if due.is_some() && target_object != "milestone" {
+        return Err("Only milestones have a due date".to_string());
+    }
+    let id = match target_object {
+        "project" => {
+            let new_project = transaction(store, |store| {
+                let new_project = Funknote {
+                    id: allocate(store)?,
+                    title: title.to_string(),
+                    description: String::new(),
+                    created_on: now_timestamp(),
+                    active: true,
+                    objects: Vec::new(),
+                    milestones: Vec::new(),
+                };
+                store.save_project(&new_project).map_err(write_error)?;
+                Ok(new_project)
+            })?;
+
+            // New projects become the current one
+            if select {
+                state.current_project_id = Some(new_project.id);
+                state.current_object_id = None;
+            }
+            println!("Created project '{}' (id {})", new_project.title, new_project.id);
+            new_project.id
+        }
+        "object" => {
+            let project_id = current_project(state)?;
+            let (new_object, project) = transaction(store, |store| {
+                let project = require_project(store, project_id)?;
+                let new_object = Object {
+                    id: allocate(store)?,
+                    project_id,
+                    title: title.to_string(),
+                    description: String::new(),
+                    created_on: now_timestamp(),
+                    active: true,
+                    items: Vec::new(),
+                };
+                store.save_object(&new_object).map_err(write_error)?;
+                Ok((new_object, project))
+            })?;
+            println!("Created object '{}' (id {}) in '{}'", new_object.title, new_object.id, project.title);
 
-pub enum actions {
+            // New objects become the current one, so items can go straight in.
+            // The project may have come from the primary, so select that too.
+            if select {
+                state.current_project_id = Some(project_id);
+                state.current_object_id = Some(new_object.id);
+            }
+            new_object.id
+        }
+        "item" => {
+            let object_id = state
+                .current_object_id
+                .ok_or("No object selected. Create one with new(object, \"Name\") or pick one with use(object, \"Name\")")?;
+            let (new_item, object) = transaction(store, |store| {
+                let object = store
+                    .load_object(object_id)
+                    .map_err(read_error)?
+                    .ok_or_else(|| format!("Current object {} no longer exists", object_id))?;
+                let new_item = Item {
+                    id: allocate(store)?,
+                    object_id,
+                    text: title.to_string(),
+                    created_on: now_timestamp(),
+                    completed: false,
+                    completed_on: None,
+                };
+                store.save_item(&new_item).map_err(write_error)?;
+                Ok((new_item, object))
+            })?;
+            println!("Created item '{}' (id {}) in '{}'", new_item.text, new_item.id, object.title);
+            new_item.id
+        }
+        "milestone" => {
+            let project_id = current_project(state)?;
+            // Track the current object if there is one, otherwise the whole project
+            let target = match state.current_object_id {
+                Some(object_id) => MilestoneTarget::Object(object_id),
+                None => MilestoneTarget::Project(project_id),
+            };
+            let (new_milestone, project) = transaction(store, |store| {
+                let project = require_project(store, project_id)?;
+                let new_milestone = Milestone {
+                    id: allocate(store)?,
+                    project_id,
+                    title: title.to_string(),
+                    description: String::new(),
+                    target_date: due.unwrap_or(0), // 0 for no target date yet
+                    completed: false,
+                    completed_on: None,
+                    target,
+                };
+                store.save_milestone(&new_milestone).map_err(write_error)?;
+                Ok((new_milestone, project))
+            })?;
+            println!("Created milestone '{}' (id {}) in '{}'", new_milestone.title, new_milestone.id, project.title);
+            new_milestone.id
+        }
+        _ => return Err(format!("Invalid target object: {}", target_object)),
+    };
 
-    create,
-    delete,
-    list,
+    Ok(id)
 }
 
-// Here we'll do all the actual function work with the types
-pub fn new_method(target_object: &str, title: &str) {
-    // Implement the logic for the new method here
-    // Check what args are coming in and handle accordingly
-    // just testing output for now with a println
-    println!("Creating a new '{}' with title '{}'", target_object, title);
+// Deleting moves the record, and everything under it, to the trash
+pub fn delete_method(target_object: &str, name: &str, force: bool, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    let kind = NodeKind::from_name(target_object)
+        .ok_or_else(|| format!("Invalid target object: {}", target_object))?;
+
+    let entry = transaction(store, |store| {
+        let notebook = load(store)?;
+        let id = find_node(&notebook, state, kind, name)?;
+
+        // See what would go with it before deleting anything
+        let children = record_count(&take_subtree(&mut notebook.clone(), kind, id)) - 1;
+        if children > 0 && !force {
+            return Err(format!(
+                "{} '{}' still has {} record(s) under it. Use delete({}, \"{}\", force) to delete them too",
+                kind.name(), name, children, kind.name(), name
+            ));
+        }
+        store.delete(kind, id).map_err(write_error)
+    })?;
+    let removed = &entry.contents;
+
+    // Don't leave the current selection pointing into the trash
+    if state.current_project_id.is_some_and(|p| removed.notes.iter().any(|n| n.id == p)) {
+        state.current_project_id = None;
+    }
+    if state.current_object_id.is_some_and(|o| removed.objects.iter().any(|ob| ob.id == o)) {
+        state.current_object_id = None;
+    }
+    if state.primary_project_id.is_some_and(|p| removed.notes.iter().any(|n| n.id == p)) {
+        state.primary_project_id = None;
+    }
+
+    println!(
+        "Moved {} '{}' and {} record(s) under it to the trash. Bring it back with restore(\"{}\").",
+        kind.name(), name, record_count(removed) - 1, name
+    );
+    Ok(())
+}
+
+// Restores the most recently deleted match. With no type given, any kind matches.
+pub fn restore_method(target_object: Option<&str>, name: &str, store: &mut dyn Store) -> Result<(), String> {
+    let kind = match target_object {
+        Some(t) => Some(NodeKind::from_name(t).ok_or_else(|| format!("Invalid target object: {}", t))?),
+        None => None,
+    };
+    let kind = transaction(store, |store| restore_entry(store, kind, name))?;
+    println!("Restored {} '{}' from the trash.", kind.name(), name);
+    Ok(())
+}
+
+// Moves the newest matching trash entry back into the notebook, returning its kind
+fn restore_entry(store: &mut dyn Store, kind: Option<NodeKind>, name: &str) -> Result<NodeKind, String> {
+    let mut notebook = load(store)?;
+
+    let position = notebook
+        .trash
+        .iter()
+        .rposition(|e| kind.is_none_or(|k| k == e.kind) && trash_title(e) == name)
+        .ok_or_else(|| {
+            let titles: Vec<String> = notebook.trash.iter().map(trash_title).collect();
+            if titles.is_empty() {
+                "The trash is empty".to_string()
+            } else {
+                format!("Nothing called '{}' in the trash. It holds: {}", name, titles.join(", "))
+            }
+        })?;
+    let entry = notebook.trash.remove(position);
+    let contents = entry.contents;
+
+    // Hang the record back on its parent, which has to be live
+    match entry.kind {
+        NodeKind::Project => {}
+        NodeKind::Object => {
+            let project_id = contents.objects.iter().find(|o| o.id == entry.id).map_or(0, |o| o.project_id);
+            match notebook.note_mut(project_id) {
+                Some(project) => project.objects.push(entry.id),
+                None => return Err(missing_parent(&notebook.trash, NodeKind::Project, project_id, name)),
+            }
+        }
+        NodeKind::Item => {
+            let object_id = contents.items.iter().find(|i| i.id == entry.id).map_or(0, |i| i.object_id);
+            match notebook.object_mut(object_id) {
+                Some(object) => object.items.push(entry.id),
+                None => return Err(missing_parent(&notebook.trash, NodeKind::Object, object_id, name)),
+            }
+        }
+        NodeKind::Milestone => {}
+    }
+    // Milestones are always listed on their project, even when deleted alongside an object
+    for milestone in contents.milestones.iter().filter(|m| !contents.notes.iter().any(|n| n.id == m.project_id)) {
+        match notebook.note_mut(milestone.project_id) {
+            Some(project) => project.milestones.push(milestone.id),
+            None => return Err(missing_parent(&notebook.trash, NodeKind::Project, milestone.project_id, name)),
+        }
+    }
+
+    notebook.notes.extend(contents.notes);
+    notebook.objects.extend(contents.objects);
+    notebook.items.extend(contents.items);
+    notebook.milestones.extend(contents.milestones);
+    save(store, &notebook)?;
+    Ok(entry.kind)
+}
+
+// Which record an edit applies to
+pub enum Target<'a> {
+    Current,                    // The current object, else the current project
+    Named(&'a str, &'a str),    // A type and a name
+    Record(NodeKind, usize),    // One already known, like the subject of a chain
+}
+
+// Sets the title (or an item's text) of a record, returning which one
+pub fn title_method(target: Target, title: &str, state: &FunkState, store: &mut dyn Store) -> Result<(NodeKind, usize), String> {
+    let changed = transaction(store, |store| {
+        let notebook = load(store)?;
+        let (kind, id) = resolve_target(&notebook, state, &target)?;
+        let gone = || format!("{} {} no longer exists", kind.name(), id);
+
+        match kind {
+            NodeKind::Project => {
+                let mut note = store.load_project(id).map_err(read_error)?.ok_or_else(gone)?;
+                note.title = title.to_string();
+                store.update_project(&note)
+            }
+            NodeKind::Object => {
+                let mut object = store.load_object(id).map_err(read_error)?.ok_or_else(gone)?;
+                object.title = title.to_string();
+                store.update_object(&object)
+            }
+            NodeKind::Item => {
+                let mut item = store.load_item(id).map_err(read_error)?.ok_or_else(gone)?;
+                item.text = title.to_string();
+                store.update_item(&item)
+            }
+            NodeKind::Milestone => {
+                let mut milestone = store.load_milestone(id).map_err(read_error)?.ok_or_else(gone)?;
+                milestone.title = title.to_string();
+                store.update_milestone(&milestone)
+            }
+        }
+        .map_err(write_error)?;
+        Ok((kind, id))
+    })?;
+
+    println!("Set title to: {}", title);
+    Ok(changed)
+}
+
+// Sets the description of a record, returning which one
+pub fn describe_method(target: Target, description: &str, state: &FunkState, store: &mut dyn Store) -> Result<(NodeKind, usize), String> {
+    let changed = transaction(store, |store| {
+        let notebook = load(store)?;
+        let (kind, id) = resolve_target(&notebook, state, &target)?;
+        let gone = || format!("{} {} no longer exists", kind.name(), id);
+
+        match kind {
+            NodeKind::Project => {
+                let mut note = store.load_project(id).map_err(read_error)?.ok_or_else(gone)?;
+                note.description = description.to_string();
+                store.update_project(&note)
+            }
+            NodeKind::Object => {
+                let mut object = store.load_object(id).map_err(read_error)?.ok_or_else(gone)?;
+                object.description = description.to_string();
+                store.update_object(&object)
+            }
+            NodeKind::Item => return Err("Items don't have descriptions, only text. Use title() to change it".to_string()),
+            NodeKind::Milestone => {
+                let mut milestone = store.load_milestone(id).map_err(read_error)?.ok_or_else(gone)?;
+                milestone.description = description.to_string();
+                store.update_milestone(&milestone)
+            }
+        }
+        .map_err(write_error)?;
+        Ok((kind, id))
+    })?;
+
+    println!("Set description to: {}", description);
+    Ok(changed)
+}
+
+// ============ Progress ============
+
+// A record given by its ID, or by its title (text, for items)
+pub enum RecordRef<'a> {
+    Id(usize),
+    Name(&'a str),
+}
+
+// Marks an item done, or not done again. Returns its ID.
+pub fn done_method(item: RecordRef, completed: bool, state: &FunkState, store: &mut dyn Store) -> Result<usize, String> {
+    let item = transaction(store, |store| {
+        let id = resolve_ref(store, state, NodeKind::Item, &item)?;
+        let mut item = store.load_item(id).map_err(read_error)?.ok_or(format!("item {} doesn't exist", id))?;
+        item.completed = completed;
+        item.completed_on = completed.then(now_timestamp);
+        store.update_item(&item).map_err(write_error)?;
+        Ok(item)
+    })?;
+
+    let status = if completed { "Done" } else { "Not done" };
+    println!("{}: {}", status, item.text);
+    Ok(item.id)
+}
+
+// Sets when a milestone is due. Returns its ID.
+pub fn due_method(milestone: RecordRef, date: u64, state: &FunkState, store: &mut dyn Store) -> Result<usize, String> {
+    let milestone = transaction(store, |store| {
+        let id = resolve_ref(store, state, NodeKind::Milestone, &milestone)?;
+        let mut milestone = store.load_milestone(id).map_err(read_error)?.ok_or(format!("milestone {} doesn't exist", id))?;
+        milestone.target_date = date;
+        store.update_milestone(&milestone).map_err(write_error)?;
+        Ok(milestone)
+    })?;
+
+    println!("{} is due {}", milestone.title, timestamp_to_string(date));
+    Ok(milestone.id)
+}
+
+fn resolve_ref(store: &mut dyn Store, state: &FunkState, kind: NodeKind, record: &RecordRef) -> Result<usize, String> {
+    match record {
+        RecordRef::Id(id) => Ok(*id),
+        RecordRef::Name(name) => find_node(&load(store)?, state, kind, name),
+    }
+}
+
+// ============ Navigation ============
+
+pub fn use_method(target_object: &str, name: &str, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    let notebook = load(store)?;
 
     match target_object {
         "project" => {
-            let new_project = Funknote {
-                id: 0, // This would be generated
-                title: title.to_string(),
-                description: String::new(),
-                created_on: now_timestamp(),
-                active: true,
-                objects: Vec::new(),
-                milestones: Vec::new(),
+            // Projects are looked up everywhere, not just under the current selection
+            let id = find_project(store, name)?;
+            state.current_project_id = Some(id);
+            state.current_object_id = None;
+        }
+        "object" => {
+            let id = find_node(&notebook, state, NodeKind::Object, name)?;
+            let project_id = notebook.objects.iter().find(|o| o.id == id).map(|o| o.project_id);
+            state.current_project_id = project_id;
+            state.current_object_id = Some(id);
+        }
+        _ => return Err(format!("Can't select a {}; only projects and objects", target_object)),
+    }
+
+    println!("Now in: {}", location(&notebook, state));
+    Ok(())
+}
+
+// Like a shell: ".." goes up, otherwise into an object of the current project,
+// or into a project when nothing is selected yet
+pub fn cd_method(name: &str, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    if name == ".." {
+        return up_method(state, store);
+    }
+
+    match state.current_project_id {
+        Some(_) => use_method("object", name, state, store),
+        None => use_method("project", name, state, store),
+    }
+}
+
+pub fn up_method(state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    if state.current_object_id.is_some() {
+        state.current_object_id = None;
+    } else if state.current_project_id.is_some() {
+        state.current_project_id = None;
+    } else {
+        return Err("Already at the root".to_string());
+    }
+
+    where_method(state, store)
+}
+
+pub fn root_method(state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    state.current_project_id = None;
+    state.current_object_id = None;
+    where_method(state, store)
+}
+
+pub fn where_method(state: &FunkState, store: &mut dyn Store) -> Result<(), String> {
+    let notebook = load(store)?;
+    println!("Now in: {}", location(&notebook, state));
+    Ok(())
+}
+
+pub fn back_method(state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    let notebook = load(store)?;
+    // Skip over places that have been deleted since
+    let pos = (0..state.history_pos)
+        .rev()
+        .find(|&i| location_exists(&notebook, &state.history[i]))
+        .ok_or("Nothing to go back to")?;
+
+    go_to_history(state, pos);
+    println!("Now in: {}", location(&notebook, state));
+    Ok(())
+}
+
+pub fn forward_method(state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    let notebook = load(store)?;
+    let pos = (state.history_pos + 1..state.history.len())
+        .find(|&i| location_exists(&notebook, &state.history[i]))
+        .ok_or("Nothing to go forward to")?;
+
+    go_to_history(state, pos);
+    println!("Now in: {}", location(&notebook, state));
+    Ok(())
+}
+
+pub fn history_method(state: &FunkState, store: &mut dyn Store) -> Result<(), String> {
+    let notebook = load(store)?;
+    if state.history.is_empty() {
+        println!("No history yet.");
+        return Ok(());
+    }
+
+    for (i, visited) in state.history.iter().enumerate() {
+        let visited_state = FunkState {
+            current_project_id: visited.project_id,
+            current_object_id: visited.object_id,
+            primary_project_id: state.primary_project_id,
+            ..FunkState::default()
+        };
+        let marker = if i == state.history_pos { ">" } else { " " };
+        println!("{} {:>2}. {}", marker, i + 1, location(&notebook, &visited_state));
+    }
+    Ok(())
+}
+
+// Navigation state lives in the metadata file between runs
+pub fn load_state(store: &mut dyn Store) -> FunkState {
+    store.read_state().unwrap_or_else(|e| {
+        println!("Warning: couldn't read saved state, starting at the root: {}", e);
+        FunkState::default()
+    })
+}
+
+// Records where we ended up in the history, then saves
+pub fn save_state(state: &mut FunkState, store: &mut dyn Store) {
+    record_visit(state);
+    if let Err(e) = store.write_state(state) {
+        println!("Warning: couldn't save state: {}", e);
+    }
+}
+
+// ============ File format ============
+
+// Upgrades the saved notes to the current format, or just says what would change
+pub fn migrate_method(dry_run: bool, store: &mut dyn Store) -> Result<(), String> {
+    let report = store.migrate(dry_run).map_err(|e| format!("Failed to migrate notes: {}", e))?;
+
+    if report.steps.is_empty() {
+        println!("The notes are already at format version {}; nothing to migrate.", report.to);
+        return Ok(());
+    }
+    let verb = if dry_run { "Would migrate" } else { "Migrated" };
+    println!("{} the notes from format version {} to {}:", verb, report.from, report.to);
+    for step in &report.steps {
+        println!("  {}", step);
+    }
+    if let Some(backup) = &report.backup {
+        println!("The old file was saved as {}", backup.display());
+    }
+    Ok(())
+}
+
+pub fn check_method(store: &mut dyn Store) -> Result<(), String> {
+    let problems = store.check().map_err(|e| format!("Failed to check notes: {}", e))?;
+
+    if problems.is_empty() {
+        println!("No problems found.");
+        return Ok(());
+    }
+    for problem in &problems {
+        println!("  {}", problem);
+    }
+    Err(format!("Found {} problem(s) with the notes", problems.len()))
+}
+
+// ============ Export and import ============
+
+// Everything, or just the named project
+pub fn export_method(format: &str, project: Option<&str>, path: &str, store: &mut dyn Store) -> Result<(), String> {
+    let mut notebook = load(store)?;
+    if let Some(name) = project {
+        let id = find_project(store, name)?;
+        notebook = take_subtree(&mut notebook, NodeKind::Project, id);
+    }
+
+    let rendered = match format {
+        "json" => export::notebook_to_json(&notebook),
+        "csv" => export::notebook_to_csv(&notebook),
+        "markdown" => {
+            let projects: Vec<String> = notebook.notes.iter()
+                .map(|note| export::project_to_markdown(note, &notebook))
+                .collect();
+            projects.join("\n")
+        }
+        _ => return Err(format!("Can't export as {}", format)),
+    };
+
+    std::fs::write(path, rendered).map_err(|e| format!("Failed to write {}: {}", path, e))?;
+    println!(
+        "Exported {} project(s) ({} records) to {}",
+        notebook.notes.len(), record_count(&notebook), path
+    );
+    Ok(())
+}
+
+// Imported records get fresh IDs, so they never clash with what's in the store
+pub fn import_method(format: &str, path: &str, store: &mut dyn Store) -> Result<(), String> {
+    let contents = std::fs::read_to_string(path).map_err(|e| format!("Failed to read {}: {}", path, e))?;
+    let mut imported = match format {
+        "json" => export::notebook_from_json(&contents),
+        "markdown" => {
+            // Tasks before the first heading go in a project named after the file
+            let stem = std::path::Path::new(path).file_stem().map(|s| s.to_string_lossy().into_owned());
+            export::notebook_from_markdown(&contents, stem.as_deref().unwrap_or(path), now_timestamp())
+        }
+        _ => return Err(format!("Can't import from {}", format)),
+    }
+    .map_err(|e| format!("Can't import {}: {}", path, e))?;
+
+    transaction(store, |store| {
+        export::renumber(&mut imported, || allocate(store))?;
+        let mut notebook = load(store)?;
+        notebook.notes.extend(imported.notes.iter().cloned());
+        notebook.objects.extend(imported.objects.iter().cloned());
+        notebook.items.extend(imported.items.iter().cloned());
+        notebook.milestones.extend(imported.milestones.iter().cloned());
+        save(store, &notebook)
+    })?;
+
+    println!("Imported {} project(s) ({} records) from {}", imported.notes.len(), record_count(&imported), path);
+    for note in &imported.notes {
+        println!("  {} (id {})", note.title, note.id);
+    }
+    Ok(())
+}
+
+// ============ Primary project ============
+
+pub fn show_primary(state: &FunkState, store: &mut dyn Store) -> Result<(), String> {
+    match state.primary_project_id {
+        Some(id) => {
+            let notebook = load(store)?;
+            match notebook.notes.iter().find(|n| n.id == id) {
+                Some(note) => println!("Primary project: {}", note.title),
+                None => println!("The primary project ({}) no longer exists.", id),
+            }
+        }
+        None => println!("No primary project set."),
+    }
+    Ok(())
+}
+
+// Marks a project as primary, or clears the primary with None
+pub fn set_primary(name: Option<&str>, state: &mut FunkState, store: &mut dyn Store) -> Result<(), String> {
+    match name {
+        Some(name) => {
+            let id = find_project(store, name)?;
+            state.primary_project_id = Some(id);
+            println!("Primary project: {}", name);
+        }
+        None => {
+            state.primary_project_id = None;
+            println!("Primary project cleared.");
+        }
+    }
+    Ok(())
+}
+
+// ============ Listing ============
+
+// Everything of one kind: all projects, or the objects, items or milestones
+// under the current selection
+pub fn list_kind(target_object: &str, state: &FunkState, store: &mut dyn Store) -> Result<(), String> {
+    let kind = NodeKind::from_name(target_object)
+        .ok_or_else(|| format!("Invalid target object: {}", target_object))?;
+    let notebook = load(store)?;
+    print!("{}", render_kind_list(&notebook, state, kind)?);
+    Ok(())
+}
+
+// The children of one record. With no name, the current (or primary) project's.
+pub fn list_children(target_object: &str, name: Option<&str>, state: &FunkState, store: &mut dyn Store) -> Result<(), String> {
+    let kind = NodeKind::from_name(target_object)
+        .ok_or_else(|| format!("Invalid target object: {}", target_object))?;
+    let notebook = load(store)?;
+    let id = match name {
+        Some(name) if kind == NodeKind::Project => find_project(store, name)?,
+        // Listing only reads, so a name from outside the current selection is fine
+        Some(name) => find_node(&notebook, state, kind, name)
+            .or_else(|_| find_node(&notebook, &FunkState::default(), kind, name))?,
+        None => current_project(state)?,
+    };
+    print!("{}", render_children(&notebook, kind, id)?);
+    Ok(())
+}
+
+fn render_kind_list(notebook: &Notebook, state: &FunkState, kind: NodeKind) -> Result<String, String> {
+    let listing = match kind {
+        NodeKind::Project => {
+            if notebook.notes.is_empty() {
+                return Ok("No projects yet. Create one with new(\"Name\").\n".to_string());
+            }
+            render_projects(&notebook.notes.iter().collect::<Vec<_>>(), state)
+        }
+        NodeKind::Object => {
+            let project_id = current_project(state)?;
+            let objects: Vec<&Object> = notebook.objects.iter().filter(|o| o.project_id == project_id).collect();
+            render_objects(notebook, &objects)
+        }
+        NodeKind::Item => {
+            // Items of the current object, or of the whole current project
+            let items: Vec<&Item> = match state.current_object_id {
+                Some(object_id) => notebook.items.iter().filter(|i| i.object_id == object_id).collect(),
+                None => {
+                    let project_id = current_project(state)?;
+                    let objects: Vec<usize> = notebook.objects.iter()
+                        .filter(|o| o.project_id == project_id)
+                        .map(|o| o.id)
+                        .collect();
+                    notebook.items.iter().filter(|i| objects.contains(&i.object_id)).collect()
+                }
             };
-            write_funknote_to_file(&new_project).expect("Failed to write new project to storage");
-            println!("New project created: {:?}", new_project);
+            render_items(&items)
+        }
+        NodeKind::Milestone => {
+            let project_id = current_project(state)?;
+            let milestones: Vec<&Milestone> = notebook.milestones.iter().filter(|m| m.project_id == project_id).collect();
+            render_milestones(notebook, &milestones)
+        }
+    };
+    Ok(listing)
+}
+
+fn render_children(notebook: &Notebook, kind: NodeKind, id: usize) -> Result<String, String> {
+    let listing = match kind {
+        NodeKind::Project => {
+            let objects: Vec<&Object> = notebook.objects.iter().filter(|o| o.project_id == id).collect();
+            let milestones: Vec<&Milestone> = notebook.milestones.iter().filter(|m| m.project_id == id).collect();
+            let mut listing = render_objects(notebook, &objects);
+            if !milestones.is_empty() {
+                listing.push('\n');
+                listing.push_str(&render_milestones(notebook, &milestones));
+            }
+            listing
+        }
+        NodeKind::Object => {
+            let items: Vec<&Item> = notebook.items.iter().filter(|i| i.object_id == id).collect();
+            render_items(&items)
+        }
+        NodeKind::Item => {
+            // An item's only "children" are the milestones tracking it
+            let milestones: Vec<&Milestone> = notebook.milestones.iter()
+                .filter(|m| m.target == MilestoneTarget::Item(id))
+                .collect();
+            render_milestones(notebook, &milestones)
+        }
+        NodeKind::Milestone => {
+            return Err("Milestones have no children. show_project() shows what they track".to_string());
         }
-        "object" => println!("Creating a new object with title '{}'", title),
-        "item" => println!("Creating a new item with title '{}'", title),
-        "milestone" => println!("Creating a new milestone with title '{}'", title),
-        _ => println!("Invalid target object: {}", target_object),
+    };
+    Ok(listing)
+}
+
+fn render_projects(notes: &[&Funknote], state: &FunkState) -> String {
+    let mut listing = format!("{:<5} {:<9} {:<11} Title\n", "ID", "Status", "Created");
+    for note in notes {
+        let status = if note.active { "active" } else { "inactive" };
+        listing.push_str(&format!(
+            "{:<5} {:<9} {:<11} {}\n",
+            note.id, status, timestamp_to_string(note.created_on), project_label(note, state)
+        ));
     }
-    
+    listing
 }
 
-pub fn show_current_project() -> Result<(), Error> {
-    // Display the current project from the vector of projects
-    println!("Displaying the current project...");
-    Ok(())  
-}
\ No newline at end of file
+fn render_objects(notebook: &Notebook, objects: &[&Object]) -> String {
+    if objects.is_empty() {
+        return "No objects.\n".to_string();
+    }
+    let mut listing = format!("{:<5} {:<9} {:<11} {:<20} Title\n", "ID", "Status", "Created", "Progress");
+    for object in objects {
+        let status = if object.active { "active" } else { "inactive" };
+        listing.push_str(&format!(
+            "{:<5} {:<9} {:<11} {:<20} {}\n",
+            object.id,
+            status,
+            timestamp_to_string(object.created_on),
+            progress_label(object_progress(notebook, object)),
+            object.title
+        ));
+    }
+    listing
+}
+
+fn render_items(items: &[&Item]) -> String {
+    if items.is_empty() {
+        return "No items.\n".to_string();
+    }
+    let mut listing = format!("{:<5} {:<11} Item\n", "ID", "Created");
+    for item in items {
+        listing.push_str(&format!("{:<5} {:<11} {}\n", item.id, timestamp_to_string(item.created_on), item_line(item)));
+    }
+    listing
+}
+
+fn render_milestones(notebook: &Notebook, milestones: &[&Milestone]) -> String {
+    if milestones.is_empty() {
+        return "No milestones.\n".to_string();
+    }
+    let mut listing = format!("{:<5} Milestone\n", "ID");
+    for milestone in milestones {
+        listing.push_str(&format!("{:<5} {}\n", milestone.id, milestone_line(notebook, milestone)));
+    }
+    listing
+}
+
+// ============ Showing projects ============
+
+pub fn show_project_method(name: &str, state: &FunkState, store: &mut dyn Store) -> Result<(), String> {
+    let notebook = load(store)?;
+    let id = find_project(store, name)?;
+    print!("{}", render_project_tree(&notebook, state, id).unwrap_or_default());
+    Ok(())
+}
+
+// Displays the current project, or the primary one when nothing is selected
+pub fn show_current_project(state: &FunkState, store: &mut dyn Store) -> Result<(), String> {
+    let notebook = load(store)?;
+    let id = current_project(state)?;
+    let tree = render_project_tree(&notebook, state, id)
+        .ok_or_else(|| format!("Project {} no longer exists", id))?;
+    print!("{}", tree);
+    Ok(())
+}
+
+/// Draws a project as a tree: objects with their items, then milestones.
+pub fn render_project_tree(notebook: &Notebook, state: &FunkState, project_id: usize) -> Option<String> {
+    let note = notebook.notes.iter().find(|n| n.id == project_id)?;
+    let objects: Vec<&Object> = notebook.objects.iter().filter(|o| note.objects.contains(&o.id)).collect();
+    let milestones: Vec<&Milestone> = notebook.milestones.iter().filter(|m| note.milestones.contains(&m.id)).collect();
+
+    let mut tree = format!("{}  {}\n", project_label(note, state), progress_label(project_progress(notebook, note)));
+    let mut details = vec![format!("created {}", timestamp_to_string(note.created_on))];
+    if !note.description.is_empty() {
+        details.insert(0, note.description.clone());
+    }
+    if !note.active {
+        details.push("inactive".to_string());
+    }
+    tree.push_str(&format!("  {}\n", details.join(" · ")));
+
+    // Milestones hang off the end as one extra branch
+    let branches = objects.len() + usize::from(!milestones.is_empty());
+    for (i, object) in objects.iter().enumerate() {
+        let (branch, indent) = tree_branch(i + 1 == branches);
+        let inactive = if object.active { "" } else { " (inactive)" };
+        tree.push_str(&format!(
+            "{}{}{}  {}\n",
+            branch, object.title, inactive, progress_label(object_progress(notebook, object))
+        ));
+
+        let items: Vec<&Item> = notebook.items.iter().filter(|it| object.items.contains(&it.id)).collect();
+        for (j, item) in items.iter().enumerate() {
+            let (twig, _) = tree_branch(j + 1 == items.len());
+            tree.push_str(&format!("{}{}{}\n", indent, twig, item_line(item)));
+        }
+    }
+
+    if !milestones.is_empty() {
+        tree.push_str("└── Milestones\n");
+        for (j, milestone) in milestones.iter().enumerate() {
+            let (twig, _) = tree_branch(j + 1 == milestones.len());
+            tree.push_str(&format!("    {}{}\n", twig, milestone_line(notebook, milestone)));
+        }
+    }
+
+    Some(tree)
+}
+
+fn tree_branch(last: bool) -> (&'static str, &'static str) {
+    if last {
+        ("└── ", "    ")
+    } else {
+        ("├── ", "│   ")
+    }
+}
+
+fn checkbox(done: bool) -> &'static str {
+    if done { "[x]" } else { "[ ]" }
+}
+
+fn item_line(item: &Item) -> String {
+    match item.completed_on {
+        Some(ts) if item.completed => format!("{} {}  (done {})", checkbox(true), item.text, timestamp_to_string(ts)),
+        _ => format!("{} {}", checkbox(item.completed), item.text),
+    }
+}
+
+fn milestone_line(notebook: &Notebook, milestone: &Milestone) -> String {
+    let due = if milestone.target_date == 0 {
+        "no date".to_string()
+    } else {
+        format!("due {}", timestamp_to_string(milestone.target_date))
+    };
+
+    // What the milestone tracks, and how far along that is
+    let tracks = match milestone.target {
+        MilestoneTarget::Project(id) => notebook.notes.iter().find(|n| n.id == id)
+            .map(|n| format!("whole project {}", progress_label(project_progress(notebook, n)))),
+        MilestoneTarget::Object(id) => notebook.objects.iter().find(|o| o.id == id)
+            .map(|o| format!("{} {}", o.title, progress_label(object_progress(notebook, o)))),
+        MilestoneTarget::Item(id) => notebook.items.iter().find(|i| i.id == id)
+            .map(|i| format!("{} {}", checkbox(i.completed), i.text)),
+    }
+    .unwrap_or_else(|| "something deleted".to_string());
+
+    let mut line = format!("{} {}  {}  → {}", checkbox(milestone.completed), milestone.title, due, tracks);
+    if let Some(ts) = milestone.completed_on.filter(|_| milestone.completed) {
+        line.push_str(&format!("  (done {})", timestamp_to_string(ts)));
+    }
+    line
+}
+
+// (completed, total) items
+fn object_progress(notebook: &Notebook, object: &Object) -> (usize, usize) {
+    let items = notebook.items.iter().filter(|i| object.items.contains(&i.id));
+    items.fold((0, 0), |(done, total), item| (done + usize::from(item.completed), total + 1))
+}
+
+fn project_progress(notebook: &Notebook, note: &Funknote) -> (usize, usize) {
+    notebook
+        .objects
+        .iter()
+        .filter(|o| note.objects.contains(&o.id))
+        .map(|o| object_progress(notebook, o))
+        .fold((0, 0), |(done, total), (d, t)| (done + d, total + t))
+}
+
+fn progress_label((done, total): (usize, usize)) -> String {
+    if total == 0 {
+        "[no items]".to_string()
+    } else {
+        format!("[{}/{} done, {}%]", done, total, done * 100 / total)
+    }
+}
+
+// ============ Helpers ============
+
+// The project to act on when none is named: the current one, else the primary
+fn current_project(state: &FunkState) -> Result<usize, String> {
+    state.current_project_id.or(state.primary_project_id).ok_or_else(|| {
+        "No project selected. Create one with new(\"Name\"), pick one with use(project, \"Name\") \
+         or set a default with primary(\"Name\")"
+            .to_string()
+    })
+}
+
+// Project titles, with the primary project marked
+fn project_label(note: &Funknote, state: &FunkState) -> String {
+    if state.primary_project_id == Some(note.id) {
+        format!("{} ★", note.title)
+    } else {
+        note.title.clone()
+    }
+}
+
+fn current_location(state: &FunkState) -> Location {
+    Location {
+        project_id: state.current_project_id,
+        object_id: state.current_object_id,
+    }
+}
+
+// Browser style: going somewhere new drops anything "forward" of where we were
+fn record_visit(state: &mut FunkState) {
+    let here = current_location(state);
+    if state.history.get(state.history_pos) == Some(&here) {
+        return;
+    }
+
+    state.history.truncate(state.history_pos + 1);
+    state.history.push(here);
+    if state.history.len() > MAX_HISTORY {
+        state.history.remove(0);
+    }
+    state.history_pos = state.history.len() - 1;
+}
+
+fn go_to_history(state: &mut FunkState, pos: usize) {
+    let there = state.history[pos];
+    state.current_project_id = there.project_id;
+    state.current_object_id = there.object_id;
+    state.history_pos = pos;
+}
+
+fn location_exists(notebook: &Notebook, location: &Location) -> bool {
+    let project_ok = location.project_id.is_none_or(|p| notebook.notes.iter().any(|n| n.id == p));
+    let object_ok = location.object_id.is_none_or(|o| notebook.objects.iter().any(|ob| ob.id == o));
+    project_ok && object_ok
+}
+
+// The record an edit applies to: the one given, or else the current object,
+// or else the current project
+fn resolve_target(notebook: &Notebook, state: &FunkState, target: &Target) -> Result<(NodeKind, usize), String> {
+    match *target {
+        Target::Named(target_object, name) => {
+            let kind = NodeKind::from_name(target_object)
+                .ok_or_else(|| format!("Invalid target object: {}", target_object))?;
+            Ok((kind, find_node(notebook, state, kind, name)?))
+        }
+        Target::Record(kind, id) => Ok((kind, id)),
+        Target::Current => match state.current_object_id {
+            Some(object_id) => Ok((NodeKind::Object, object_id)),
+            None => Ok((NodeKind::Project, current_project(state)?)),
+        },
+    }
+}
+
+// A path like /Garden/Beds for the current selection
+fn location(notebook: &Notebook, state: &FunkState) -> String {
+    let project = state
+        .current_project_id
+        .and_then(|id| notebook.notes.iter().find(|n| n.id == id))
+        .map(|n| project_label(n, state));
+    let object = state
+        .current_object_id
+        .and_then(|id| notebook.objects.iter().find(|o| o.id == id))
+        .map(|o| o.title.as_str());
+
+    match (project, object) {
+        (Some(p), Some(o)) => format!("/{}/{}", p, o),
+        (Some(p), None) => format!("/{}", p),
+        _ => "/ (nothing selected)".to_string(),
+    }
+}
+
+// Find a record by its title (or text, for items). Objects, items and milestones
+// are looked up within the current selection first, when there is one.
+fn find_node(notebook: &Notebook, state: &FunkState, kind: NodeKind, name: &str) -> Result<usize, String> {
+    let project = state.current_project_id;
+    let object = state.current_object_id;
+
+    let matches: Vec<usize> = match kind {
+        NodeKind::Project => notebook.notes.iter()
+            .filter(|n| n.title == name)
+            .map(|n| n.id)
+            .collect(),
+        NodeKind::Object => notebook.objects.iter()
+            .filter(|o| o.title == name && project.is_none_or(|p| o.project_id == p))
+            .map(|o| o.id)
+            .collect(),
+        // Items of the current object, or else of any object in the current project
+        NodeKind::Item => notebook.items.iter()
+            .filter(|i| i.text == name)
+            .filter(|i| match (object, project) {
+                (Some(o), _) => i.object_id == o,
+                (None, Some(p)) => notebook.objects.iter().any(|o| o.id == i.object_id && o.project_id == p),
+                (None, None) => true,
+            })
+            .map(|i| i.id)
+            .collect(),
+        NodeKind::Milestone => notebook.milestones.iter()
+            .filter(|m| m.title == name && project.is_none_or(|p| m.project_id == p))
+            .map(|m| m.id)
+            .collect(),
+    };
+
+    match matches.as_slice() {
+        [id] => Ok(*id),
+        [] => Err(format!("No {} named '{}'", kind.name(), name)),
+        _ => Err(format!(
+            "{} {}s are named '{}'; select the one you mean first",
+            matches.len(), kind.name(), name
+        )),
+    }
+}
+
+// A project by name, from anywhere. Uses the store's title lookup, which
+// doesn't need the whole notebook.
+fn find_project(store: &mut dyn Store, name: &str) -> Result<usize, String> {
+    let matches = store.find_by_title(NodeKind::Project, name).map_err(read_error)?;
+    match matches.as_slice() {
+        [id] => Ok(*id),
+        [] => Err(format!("No project named '{}'", name)),
+        _ => Err(format!("{} projects are named '{}'; select the one you mean first", matches.len(), name)),
+    }
+}
+
+// The title (or text) of the record a trash entry was made for
+// Why a restore can't go ahead: the parent is in the trash too, or gone for good
+fn missing_parent(trash: &[TrashEntry], kind: NodeKind, id: usize, name: &str) -> String {
+    let title = trash.iter().find_map(|e| match kind {
+        NodeKind::Project => e.contents.notes.iter().find(|n| n.id == id).map(|n| n.title.clone()),
+        _ => e.contents.objects.iter().find(|o| o.id == id).map(|o| o.title.clone()),
+    });
+    match title {
+        Some(title) => format!("Restore {} '{}' (id {}) first", kind.name(), title, id),
+        None => format!("The {} '{}' belonged to (id {}) no longer exists", kind.name(), name, id),
+    }
+}
+
+fn trash_title(entry: &TrashEntry) -> String {
+    let contents = &entry.contents;
+    let title = match entry.kind {
+        NodeKind::Project => contents.notes.iter().find(|n| n.id == entry.id).map(|n| &n.title),
+        NodeKind::Object => contents.objects.iter().find(|o| o.id == entry.id).map(|o| &o.title),
+        NodeKind::Item => contents.items.iter().find(|i| i.id == entry.id).map(|i| &i.text),
+        NodeKind::Milestone => contents.milestones.iter().find(|m| m.id == entry.id).map(|m| &m.title),
+    };
+    title.cloned().unwrap_or_default()
+}
+
+// How many records a notebook holds, not counting the trash
+fn record_count(notebook: &Notebook) -> usize {
+    notebook.notes.len() + notebook.objects.len() + notebook.items.len() + notebook.milestones.len()
+}
+
+// Store calls with their errors turned into messages for the user
+fn load(store: &mut dyn Store) -> Result<Notebook, String> {
+    store.load_notebook().map_err(read_error)
+}
+
+fn save(store: &mut dyn Store, notebook: &Notebook) -> Result<(), String> {
+    store.save_notebook(notebook).map_err(write_error)
+}
+
+fn require_project(store: &mut dyn Store, project_id: usize) -> Result<Funknote, String> {
+    store
+        .load_project(project_id)
+        .map_err(read_error)?
+        .ok_or_else(|| format!("Current project {} no longer exists", project_id))
+}
+
+fn allocate(store: &mut dyn Store) -> Result<usize, String> {
+    store.allocate_id().map_err(|e| format!("Failed to allocate an ID: {}", e))
+}
+
+fn read_error(e: std::io::Error) -> String {
+    format!("Failed to read notes: {}", e)
+}
+
+fn write_error(e: std::io::Error) -> String {
+    format!("Failed to save notes: {}", e)
+}
+
+// Changes to the store happen inside a transaction, so concurrent runs don't
+// clash. It's only committed if the change succeeds.
+fn transaction<T>(
+    store: &mut dyn Store,
+    change: impl FnOnce(&mut dyn Store) -> Result<T, String>,
+) -> Result<T, String> {
+    store.begin().map_err(|e| format!("Failed to lock the notes: {}", e))?;
+    match change(store) {
+        Ok(value) => {
+            store.commit().map_err(|e| format!("Failed to save the ID counter: {}", e))?;
+            Ok(value)
+        }
+        Err(e) => {
+            store.abort();
+            Err(e)
+        }
+    }
+}
+
+// ================== TESTS ===================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::fixtures::sample_notebook;
+    use crate::store::MemoryStore;
+
+    #[test]
+    fn test_delete_needs_force_then_restores() {
+        let mut store = MemoryStore::with_notebook(sample_notebook());
+        let mut state = FunkState {
+            current_project_id: Some(1),
+            current_object_id: Some(2),
+            ..FunkState::default()
+        };
+
+        assert!(delete_method("object", "Beds", false, &mut state, &mut store).is_err());
+        assert_eq!(store.notebook, sample_notebook());
+
+        delete_method("object", "Beds", true, &mut state, &mut store).unwrap();
+        assert!(store.notebook.objects.is_empty() && store.notebook.items.is_empty());
+        assert_eq!(state.current_object_id, None);
+
+        restore_method(None, "Beds", &mut store).unwrap();
+        let restored = store.notebook.clone();
+        assert!(restored.trash.is_empty());
+        assert_eq!(restored.notes[0].objects, vec![2]);
+        assert_eq!(restored.items, sample_notebook().items);
+    }
+
+    #[test]
+    fn test_restore_names_the_parent_in_the_trash() {
+        let mut store = MemoryStore::with_notebook(sample_notebook());
+        let mut state = FunkState {
+            current_project_id: Some(1),
+            current_object_id: Some(2),
+            ..FunkState::default()
+        };
+        delete_method("item", "Buy soil", true, &mut state, &mut store).unwrap();
+        delete_method("object", "Beds", false, &mut state, &mut store).unwrap();
+        state.current_project_id = None;
+        delete_method("project", "Garden", true, &mut state, &mut store).unwrap();
+
+        let trash = store.notebook.trash.clone();
+        assert_eq!(restore_method(Some("item"), "Buy soil", &mut store).unwrap_err(), "Restore object 'Beds' (id 2) first");
+        assert_eq!(restore_method(None, "Beds", &mut store).unwrap_err(), "Restore project 'Garden' (id 1) first");
+        assert_eq!(store.notebook.trash, trash); // Nothing was taken out
+
+        for name in ["Garden", "Beds", "Buy soil"] {
+            restore_method(None, name, &mut store).unwrap();
+        }
+        assert_eq!(store.notebook.notes[0].objects, vec![2]);
+        assert_eq!(store.notebook.objects[0].items, vec![3]);
+        assert!(store.notebook.trash.is_empty());
+    }
+
+    #[test]
+    fn test_render_project_tree() {
+        let notebook = sample_notebook();
+        let state = FunkState {
+            primary_project_id: Some(1),
+            ..FunkState::default()
+        };
+
+        let tree = render_project_tree(&notebook, &state, 1).unwrap();
+        let expected = "\
+Garden ★  [1/1 done, 100%]
+  Veggie patch · created 2025-12-02
+├── Beds  [1/1 done, 100%]
+│   └── [x] Buy soil  (done 2025-12-02)
+└── Milestones
+    ├── [ ] Soil in  due 2026-01-01  → [x] Buy soil
+    └── [ ] Harvest  no date  → whole project [1/1 done, 100%]
+";
+        assert_eq!(tree, expected);
+        assert!(render_project_tree(&notebook, &state, 99).is_none());
+    }
+
+    #[test]
+    fn test_export_then_import_gets_fresh_ids() {
+        let mut store = MemoryStore::with_notebook(sample_notebook());
+        let path = std::env::temp_dir().join(format!("funk_notes_export_{}.json", std::process::id()));
+        let path = path.to_str().unwrap();
+
+        export_method("json", None, path, &mut store).unwrap();
+        import_method("json", path, &mut store).unwrap();
+        let _ = std::fs::remove_file(path);
+
+        // The copy is numbered on from the counter, with its links intact
+        let notebook = &store.notebook;
+        assert_eq!(notebook.notes.len(), 2);
+        assert_eq!(store.last_id, 10);
+        let copy = &notebook.notes[1];
+        assert_eq!((copy.id, copy.title.as_str()), (6, "Garden"));
+        assert_eq!(copy.objects, vec![7]);
+        assert_eq!(notebook.items[1].object_id, 7);
+        assert_eq!(notebook.milestones[2].target, MilestoneTarget::Item(8));
+        assert_eq!(notebook.milestones[3].target, MilestoneTarget::Project(6));
+    }
+
+    // The sample plus an empty second project, with its own "Buy soil"
+    fn two_projects() -> Notebook {
+        let mut notebook = sample_notebook();
+        notebook.notes.push(Funknote {
+            id: 8,
+            title: "Shed".to_string(),
+            description: String::new(),
+            created_on: 0,
+            active: true,
+            objects: vec![9],
+            milestones: Vec::new(),
+        });
+        notebook.objects.push(Object {
+            id: 9,
+            project_id: 8,
+            title: "Shelves".to_string(),
+            description: String::new(),
+            created_on: 0,
+            active: true,
+            items: vec![10],
+        });
+        notebook.items.push(Item { id: 10, object_id: 9, ..notebook.items[0].clone() });
+        notebook
+    }
+
+    #[test]
+    fn test_delete_stays_in_the_selection() {
+        let mut store = MemoryStore::with_notebook(two_projects());
+        let mut state = FunkState { current_project_id: Some(8), ..FunkState::default() };
+        store.notebook.objects[1].items.clear();
+        store.notebook.items.pop();
+
+        // Only the other project has one, so nothing is found
+        let err = delete_method("item", "Buy soil", true, &mut state, &mut store).unwrap_err();
+        assert_eq!(err, "No item named 'Buy soil'");
+        assert_eq!(store.notebook.items.len(), 1);
+
+        // Two of them in view is ambiguous rather than a guess
+        let mut store = MemoryStore::with_notebook(two_projects());
+        let mut state = FunkState::default();
+        let err = delete_method("item", "Buy soil", true, &mut state, &mut store).unwrap_err();
+        assert!(err.starts_with("2 items are named 'Buy soil'"), "{}", err);
+
+        state.current_object_id = Some(9);
+        delete_method("item", "Buy soil", true, &mut state, &mut store).unwrap();
+        let items: Vec<usize> = store.notebook.items.iter().map(|i| i.id).collect();
+        assert_eq!(items, vec![3]);
+    }
+
+    #[test]
+    fn test_list_each_kind_from_the_selection() {
+        let notebook = two_projects();
+        let garden = FunkState { current_project_id: Some(1), ..FunkState::default() };
+        let ids = |listing: String| -> Vec<String> {
+            listing.lines().skip(1).map(|l| l.split_whitespace().next().unwrap_or("").to_string()).collect()
+        };
+
+        let projects = render_kind_list(&notebook, &garden, NodeKind::Project).unwrap();
+        assert_eq!(ids(projects), vec!["1", "8"]);
+        let objects = render_kind_list(&notebook, &garden, NodeKind::Object).unwrap();
+        assert!(objects.contains("1/1 done") && objects.contains("Beds"), "{}", objects);
+        assert_eq!(ids(objects), vec!["2"]);
+        assert_eq!(ids(render_kind_list(&notebook, &garden, NodeKind::Item).unwrap()), vec!["3"]);
+        assert_eq!(ids(render_kind_list(&notebook, &garden, NodeKind::Milestone).unwrap()), vec!["4", "5"]);
+
+        // Items narrow to the current object when there is one
+        let shelves = FunkState { current_project_id: Some(8), current_object_id: Some(9), ..FunkState::default() };
+        assert_eq!(ids(render_kind_list(&notebook, &shelves, NodeKind::Item).unwrap()), vec!["10"]);
+        assert_eq!(render_kind_list(&notebook, &shelves, NodeKind::Milestone).unwrap(), "No milestones.\n");
+
+        assert_eq!(ids(render_children(&notebook, NodeKind::Project, 1).unwrap()), vec!["2", "", "ID", "4", "5"]);
+        assert_eq!(ids(render_children(&notebook, NodeKind::Object, 2).unwrap()), vec!["3"]);
+        assert_eq!(ids(render_children(&notebook, NodeKind::Item, 3).unwrap()), vec!["4"]);
+        assert_eq!(
+            render_kind_list(&Notebook::default(), &garden, NodeKind::Project).unwrap(),
+            "No projects yet. Create one with new(\"Name\").\n"
+        );
+    }
+
+    #[test]
+    fn test_list_falls_back_to_the_primary_project() {
+        let notebook = two_projects();
+        let primary = FunkState { primary_project_id: Some(8), ..FunkState::default() };
+        let objects = render_kind_list(&notebook, &primary, NodeKind::Object).unwrap();
+        assert!(objects.contains("Shelves") && !objects.contains("Beds"), "{}", objects);
+
+        let mut store = MemoryStore::with_notebook(notebook.clone());
+        assert!(list_children("project", None, &primary, &mut store).is_ok());
+        let err = list_children("project", None, &FunkState::default(), &mut store).unwrap_err();
+        assert!(err.starts_with("No project selected"), "{}", err);
+        assert!(render_kind_list(&notebook, &FunkState::default(), NodeKind::Milestone).is_err());
+
+        // A named record can be listed from anywhere
+        assert!(list_children("object", Some("Beds"), &primary, &mut store).is_ok());
+    }
+
+    #[test]
+    fn test_milestones_have_no_children() {
+        let mut store = MemoryStore::with_notebook(sample_notebook());
+        let state = FunkState { current_project_id: Some(1), ..FunkState::default() };
+        let err = list_children("milestone", Some("Soil in"), &state, &mut store).unwrap_err();
+        assert!(err.starts_with("Milestones have no children"), "{}", err);
+        assert!(list_children("milestone", Some("Nope"), &state, &mut store).is_err());
+        assert!(list_children("planet", None, &state, &mut store).is_err());
+    }
+
+    #[test]
+    fn test_new_records_go_under_the_selection() {
+        let mut store = MemoryStore::with_notebook(sample_notebook());
+        let mut state = FunkState::default();
+
+        // Nothing selected and no primary: only projects can be made
+        for kind in ["object", "item", "milestone"] {
+            let err = new_method(kind, "Stray", None, true, &mut state, &mut store).unwrap_err();
+            assert!(err.starts_with("No "), "{}: {}", kind, err);
+        }
+        assert_eq!(store.last_id, 5);
+
+        state.current_project_id = Some(1);
+        let object = new_method("object", "Paths", None, true, &mut state, &mut store).unwrap();
+        assert_eq!(store.load_object(object).unwrap().unwrap().project_id, 1);
+        assert_eq!(store.notebook.notes[0].objects, vec![2, object]);
+        assert_eq!(state.current_object_id, Some(object));
+
+        let item = new_method("item", "Lay gravel", None, true, &mut state, &mut store).unwrap();
+        assert_eq!(store.notebook.objects[1].items, vec![item]);
+
+        // Milestones track the current object, or the project when there isn't one
+        let date = Some(1767225600);
+        let paved = new_method("milestone", "Paved", date, true, &mut state, &mut store).unwrap();
+        let paved = store.load_milestone(paved).unwrap().unwrap();
+        assert_eq!((paved.target, paved.target_date), (MilestoneTarget::Object(object), 1767225600));
+        state.current_object_id = None;
+        let done = new_method("milestone", "All done", None, true, &mut state, &mut store).unwrap();
+        assert_eq!(store.load_milestone(done).unwrap().unwrap().target, MilestoneTarget::Project(1));
+
+        assert!(new_method("item", "No object", None, true, &mut state, &mut store).is_err());
+        assert!(new_method("object", "Dated", date, true, &mut state, &mut store).is_err());
+        assert!(new_method("planet", "Mars", None, true, &mut state, &mut store).is_err());
+
+        // A selection left behind by a delete is caught, not written under
+        state.current_project_id = Some(99);
+        let err = new_method("object", "Lost", None, true, &mut state, &mut store).unwrap_err();
+        assert!(err.contains("99"), "{}", err);
+        state.current_object_id = Some(98);
+        let err = new_method("item", "Lost", None, true, &mut state, &mut store).unwrap_err();
+        assert_eq!(err, "Current object 98 no longer exists");
+    }
+
+    #[test]
+    fn test_primary_project() {
+        let mut store = MemoryStore::with_notebook(two_projects());
+        let mut state = FunkState::default();
+
+        set_primary(Some("Shed"), &mut state, &mut store).unwrap();
+        assert_eq!(state.primary_project_id, Some(8));
+        assert!(set_primary(Some("Nope"), &mut state, &mut store).is_err());
+        assert_eq!(state.primary_project_id, Some(8));
+        assert!(show_primary(&state, &mut store).is_ok());
+
+        // With nothing selected, the primary project is where things go
+        let object = new_method("object", "Pots", None, false, &mut state, &mut store).unwrap();
+        assert_eq!(store.load_object(object).unwrap().unwrap().project_id, 8);
+        assert_eq!((state.current_project_id, state.current_object_id), (None, None));
+        assert!(show_current_project(&state, &mut store).is_ok());
+
+        // A selected project still wins over it
+        state.current_project_id = Some(1);
+        let object = new_method("object", "Paths", None, true, &mut state, &mut store).unwrap();
+        assert_eq!(store.load_object(object).unwrap().unwrap().project_id, 1);
+
+        set_primary(None, &mut state, &mut store).unwrap();
+        assert_eq!(state.primary_project_id, None);
+        state.current_project_id = None;
+        state.current_object_id = None;
+        assert!(new_method("object", "Stray", None, true, &mut state, &mut store).is_err());
+        assert!(show_current_project(&state, &mut store).is_err());
+    }
+}
@@ -1,64 +1,909 @@
-use std::path::Path;
+use std::fmt;
+use std::path::{Path, PathBuf};
 use std::fs::{self, OpenOptions};
-use std::io::{self, Write};
-use std::env;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::thread;
+use std::time::{Duration, Instant};
 
+use crate::binary_store::BinaryStore;
+use crate::store::{all_ids, check_notebook, owning_project, MigrationReport, Store};
 use crate::types::*;
 
 // Constants
-const METADATA_FILE: &str = "funk_metadata.txt";
-const SPLIT_CODE: &str = "(note.id";
+pub(crate) const METADATA_FILE: &str = "funk_metadata.txt";
 const FILE_PATH: &str = "all_notes.txt";
+// A commit's changes, kept until they're safely in the files above
+const JOURNAL_FILE: &str = "funk_journal.txt";
+// The layout of all_notes.txt written by this build. See MIGRATIONS for older ones.
+const FORMAT_VERSION: u32 = 2;
+// Set this to keep the notes somewhere other than the default data directory
+pub const HOME_VAR: &str = "FUNKNOTES_HOME";
+// Set this to pick the store (text or binary) when --store isn't given
+pub const STORE_VAR: &str = "FUNKNOTES_STORE";
+// How long to wait for another funknotes process to finish with the store
+const LOCK_TIMEOUT: Duration = Duration::from_secs(5);
+// A lock older than this was left behind by a process that died
+const STALE_LOCK: Duration = Duration::from_secs(30);
 
 
-// ====== FILE FUNCTIONS =====
-pub fn write_funknote_to_file(note: &Funknote) -> io::Result<()> {
-    
-    let note_str = base_note(note);
-    append_to_file(FILE_PATH, &note_str);
-    println!("Note written to file.");
-    Ok(())
+// ====== THE TEXT STORE =====
+/// The notes as text: all_notes.txt for the notes themselves and
+/// funk_metadata.txt for the ID counter and navigation state.
+pub struct TextStore {
+    notes_path: PathBuf,
+    meta_path: PathBuf,
+    journal_path: PathBuf,
+    tx: Option<Transaction>,    // The change in progress, if any
 }
 
-/// Read next ID from counter file, increment file, return the ID.
-pub fn get_next_id() -> Result<usize, std::io::Error> {
-    // Make sure file exists. If not, create it with "1".
-    if !Path::new(METADATA_FILE).exists() {
-        fs::write(METADATA_FILE, base_meta(1))?;
-        return Ok(1 as usize);
-    }
-    
-    let contents = fs::read_to_string(METADATA_FILE)?.trim().to_string();  // ← Use ?
-    
-    let value: i64 = contents
-        .split_once('=')
-        .and_then(|(_, right)| right.trim_end_matches(')').parse::<i64>().ok())
-        .ok_or_else(|| std::io::Error::new(
-            std::io::ErrorKind::InvalidData,
-            "Invalid counter format"
+impl TextStore {
+    /// The text store kept in the given directory, which is created if need be.
+    /// A change that was interrupted last time is finished or thrown away first.
+    pub fn open(dir: &Path) -> io::Result<TextStore> {
+        fs::create_dir_all(dir)?;
+        let store = TextStore {
+            notes_path: dir.join(FILE_PATH),
+            meta_path: dir.join(METADATA_FILE),
+            journal_path: dir.join(JOURNAL_FILE),
+            tx: None,
+        };
+        store.recover()?;
+        Ok(store)
+    }
+
+    // The notes file as it stands, including changes not committed yet
+    fn read_notes(&self) -> io::Result<String> {
+        if let Some(notes) = self.tx.as_ref().and_then(|tx| tx.notes.as_ref()) {
+            return Ok(notes.clone());
+        }
+        if !self.notes_path.exists() {
+            return Ok(render_header(FORMAT_VERSION)); // Nothing written yet
+        }
+        // Files from older builds are upgraded as they're read, and saved in
+        // the new format the next time anything is written
+        let (contents, _) = upgrade(&read_from_file(&self.notes_path)?)?;
+        Ok(contents)
+    }
+
+    // Parse the notes, with errors pointing at lines of the file as it is on
+    // disk even when it's in an older format
+    fn parse_notes(&self) -> io::Result<Result<Notebook, Vec<ParseError>>> {
+        if self.tx.as_ref().is_some_and(|tx| tx.notes.is_some()) || !self.notes_path.exists() {
+            return Ok(parse_notebook(&self.read_notes()?));
+        }
+
+        let original = read_from_file(&self.notes_path)?;
+        let (contents, _) = upgrade(&original)?;
+        // Migrations keep every line where it was, bar the header they add
+        let added = contents.lines().count().saturating_sub(original.lines().count());
+        Ok(parse_notebook(&contents).map_err(|errors| {
+            errors
+                .into_iter()
+                .map(|e| ParseError { line: e.line.saturating_sub(added).max(1), ..e })
+                .collect()
+        }))
+    }
+
+    // Inside a transaction the new contents wait for commit(). Outside one
+    // they're written straight away, still under the lock and the journal.
+    fn write_notes(&mut self, contents: String) -> io::Result<()> {
+        match self.tx.as_mut() {
+            Some(tx) => {
+                tx.notes = Some(contents);
+                Ok(())
+            }
+            None => {
+                let _lock = LockFile::acquire(&self.meta_path)?;
+                self.apply(&Journal { notes: Some(contents), ..Journal::default() })
+            }
+        }
+    }
+
+    fn apply(&self, journal: &Journal) -> io::Result<()> {
+        apply_journal(&self.journal_path, journal, &self.notes_path, &self.meta_path)
+    }
+
+    fn recover(&self) -> io::Result<()> {
+        recover_journal(&self.journal_path, &self.notes_path, &self.meta_path)
+    }
+}
+
+impl Store for TextStore {
+    /// Read the whole store (notes, objects, items and milestones) from the notes file.
+    fn load_notebook(&mut self) -> io::Result<Notebook> {
+        self.parse_notes()?.map_err(|errors| {
+            let first = &errors[0];
+            let more = match errors.len() {
+                1 => String::new(),
+                n => format!(" (and {} more; run check() to see them all)", n - 1),
+            };
+            io::Error::new(io::ErrorKind::InvalidData, format!("{} {}{}", FILE_PATH, first, more))
+        })
+    }
+
+    /// Rewrite the notes file so it holds exactly the given notebook.
+    fn save_notebook(&mut self, notebook: &Notebook) -> io::Result<()> {
+        self.write_notes(render_notebook(notebook))
+    }
+
+    /// Rewrite just the chunk of the file for the project the record lives in
+    /// (with everything under it), leaving the rest of the file untouched.
+    /// A new project's chunk goes on the end.
+    fn write_record(&mut self, notebook: &Notebook, kind: NodeKind, id: usize) -> io::Result<()> {
+        let note_id = owning_project(notebook, kind, id).ok_or_else(|| io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("Can't tell which project {} {} belongs to", kind.name(), id),
         ))?;
-    
-    let value = value + 1;
-    
-    // Safe conversion with validation
-    let value_usize = usize::try_from(value)
-        .map_err(|_| std::io::Error::new(
+        let mut contents = self.read_notes()?;
+
+        if find_note_starts(&contents, note_id).is_empty() {
+            let note = notebook.notes.iter().find(|n| n.id == note_id).ok_or_else(|| io::Error::new(
+                io::ErrorKind::NotFound,
+                format!("Note {} not in notebook", note_id),
+            ))?;
+            contents.push_str(&base_note(note, notebook));
+            return self.write_notes(contents);
+        }
+        let updated = replace_note_chunk(&contents, notebook, note_id)?;
+        self.write_notes(updated)
+    }
+
+    fn begin(&mut self) -> io::Result<()> {
+        if self.tx.is_some() {
+            return Err(io::Error::other("A change is already in progress"));
+        }
+        self.tx = Some(Transaction::begin(&self.meta_path)?);
+        Ok(())
+    }
+
+    fn allocate_id(&mut self) -> io::Result<usize> {
+        let tx = self.tx.as_mut().ok_or_else(|| io::Error::other("No change in progress"))?;
+        Ok(tx.allocate_id())
+    }
+
+    /// Write the new notes and counter together, then release the lock.
+    fn commit(&mut self) -> io::Result<()> {
+        let tx = self.tx.take().ok_or_else(|| io::Error::other("No change in progress"))?;
+        let journal = Journal {
+            last_id: tx.allocated.then_some(tx.last_id),
+            notes: tx.notes.clone(),
+            ..Journal::default()
+        };
+        if journal == Journal::default() {
+            return Ok(()); // Nothing changed
+        }
+        self.apply(&journal)
+    }
+
+    fn abort(&mut self) {
+        self.tx = None; // Dropping the transaction releases the lock
+    }
+
+    /// Upgrade the notes file on disk, keeping a copy of the old one.
+    fn migrate(&mut self, dry_run: bool) -> io::Result<MigrationReport> {
+        let _lock = LockFile::acquire(&self.meta_path)?;
+        if !self.notes_path.exists() {
+            return Ok(MigrationReport { from: FORMAT_VERSION, to: FORMAT_VERSION, ..MigrationReport::default() });
+        }
+
+        let contents = read_from_file(&self.notes_path)?;
+        let from = file_version(&contents)?;
+        let (migrated, steps) = upgrade(&contents)?;
+        let report = MigrationReport {
+            from,
+            to: FORMAT_VERSION,
+            steps: steps.iter().map(|m| format!("v{} -> v{}: {}", m.from, m.from + 1, m.description)).collect(),
+            backup: None,
+        };
+        if dry_run || steps.is_empty() {
+            return Ok(report);
+        }
+
+        let backup = self.notes_path.with_extension(format!("v{}.bak", from));
+        fs::copy(&self.notes_path, &backup)?;
+        self.apply(&Journal { notes: Some(migrated), ..Journal::default() })?;
+        Ok(MigrationReport { backup: Some(backup), ..report })
+    }
+
+    /// Everything wrong with the notes file: the first problem on each bad
+    /// line if it doesn't parse, otherwise records that don't fit together.
+    fn check(&mut self) -> io::Result<Vec<String>> {
+        let notebook = match self.parse_notes()? {
+            Ok(notebook) => notebook,
+            Err(errors) => return Ok(errors.iter().map(|e| format!("{} {}", FILE_PATH, e)).collect()),
+        };
+
+        let mut problems = check_notebook(&notebook);
+        // The counter has to stay ahead of every ID in use
+        let meta = read_metadata(&self.meta_path)?;
+        let last_id = meta_value(&meta, "next.id").and_then(|v| v.parse::<usize>().ok()).unwrap_or(0);
+        if let Some(max_id) = all_ids(&notebook).max().filter(|&id| id > last_id) {
+            problems.push(format!(
+                "The ID counter in {} is at {}, behind ID {} already in use", METADATA_FILE, last_id, max_id
+            ));
+        }
+        Ok(problems)
+    }
+
+    fn read_state(&mut self) -> io::Result<FunkState> {
+        read_state(&self.meta_path)
+    }
+
+    fn write_state(&mut self, state: &FunkState) -> io::Result<()> {
+        write_state(&self.meta_path, state)
+    }
+}
+
+// ====== DATA DIRECTORY =====
+/// Where the notes live: the --data-dir flag if given, else $FUNKNOTES_HOME,
+/// else funknotes/ under the XDG data directory ($XDG_DATA_HOME, or
+/// ~/.local/share when that isn't set).
+pub fn data_dir(flag: Option<&str>) -> io::Result<PathBuf> {
+    resolve_data_dir(flag, |key| std::env::var(key).ok())
+}
+
+// Same, with the environment passed in so tests don't depend on the real one
+fn resolve_data_dir(flag: Option<&str>, env: impl Fn(&str) -> Option<String>) -> io::Result<PathBuf> {
+    // Empty values count as unset, as they do for XDG
+    let env = |key: &str| env(key).filter(|v| !v.is_empty());
+
+    if let Some(dir) = flag {
+        return Ok(PathBuf::from(dir));
+    }
+    if let Some(dir) = env(HOME_VAR) {
+        return Ok(PathBuf::from(dir));
+    }
+    // XDG only counts absolute paths
+    if let Some(dir) = env("XDG_DATA_HOME").map(PathBuf::from).filter(|d| d.is_absolute()) {
+        return Ok(dir.join("funknotes"));
+    }
+    match env("HOME") {
+        Some(home) => Ok(Path::new(&home).join(".local/share/funknotes")),
+        None => Err(io::Error::new(
+            io::ErrorKind::NotFound,
+            format!("No home directory. Set {} or pass --data-dir", HOME_VAR),
+        )),
+    }
+}
+
+// ====== BACKENDS =====
+/// The ways of keeping the notes on disk.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Backend {
+    Text,     // all_notes.txt, readable and editable by hand
+    Binary,   // funk_notes.bin and its index, for big notebooks
+}
+
+impl Backend {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Backend::Text => "text",
+            Backend::Binary => "binary",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Result<Backend, String> {
+        match name {
+            "text" => Ok(Backend::Text),
+            "binary" => Ok(Backend::Binary),
+            _ => Err(format!("Unknown store '{}', expected text or binary", name)),
+        }
+    }
+}
+
+/// Which store to open: the --store flag if given, else $FUNKNOTES_STORE,
+/// else whichever the directory was last converted to, else the binary
+/// store if only its files are there, else text.
+pub fn backend(dir: &Path, flag: Option<&str>) -> io::Result<Backend> {
+    let env = std::env::var(STORE_VAR).ok().filter(|v| !v.is_empty());
+    let recorded = meta_value(&read_metadata(&dir.join(METADATA_FILE))?, "store").map(str::to_string);
+    let name = flag.map(str::to_string).or(env).or(recorded);
+    match name {
+        Some(name) => Backend::from_name(&name).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e)),
+        None if !dir.join(FILE_PATH).exists() && BinaryStore::exists(dir) => Ok(Backend::Binary),
+        None => Ok(Backend::Text),
+    }
+}
+
+pub fn open_backend(dir: &Path, backend: Backend) -> io::Result<Box<dyn Store>> {
+    Ok(match backend {
+        Backend::Text => Box::new(TextStore::open(dir)?),
+        Backend::Binary => Box::new(BinaryStore::open(dir)?),
+    })
+}
+
+/// Copy the notes from one store to the other and make it the one opened by
+/// default. The old store's files are left as they are.
+pub fn convert_store(dir: &Path, from: Backend, to: Backend) -> io::Result<()> {
+    if from == to {
+        return Err(io::Error::new(io::ErrorKind::InvalidInput, format!("The notes are already in the {} store", to.name())));
+    }
+    let notebook = open_backend(dir, from)?.load_notebook()?;
+
+    let mut target = open_backend(dir, to)?;
+    target.begin()?;
+    if let Err(e) = target.save_notebook(&notebook) {
+        target.abort();
+        return Err(e);
+    }
+    target.commit()?;
+
+    let meta_path = dir.join(METADATA_FILE);
+    let _lock = LockFile::acquire(&meta_path)?;
+    let mut meta = read_metadata(&meta_path)?;
+    set_meta_value(&mut meta, "store", Some(to.name().to_string()));
+    write_metadata(&meta_path, &meta)
+}
+
+// ====== NOTE CHUNKS =====
+fn replace_note_chunk(contents: &str, notebook: &Notebook, note_id: usize) -> io::Result<String> {
+    let note = notebook
+        .notes
+        .iter()
+        .find(|n| n.id == note_id)
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, format!("Note {} not in notebook", note_id)))?;
+
+    let (start, end) = find_note_chunk(contents, note_id)?;
+
+    // Rendered chunks come wrapped in blank lines, which are already in the file
+    let chunk = base_note(note, notebook);
+    let mut updated = String::with_capacity(contents.len() + chunk.len());
+    updated.push_str(&contents[..start]);
+    updated.push_str(chunk.trim_matches('\n'));
+    updated.push_str(&contents[end..]);
+    Ok(updated)
+}
+
+// Byte range of a note's chunk, from the start of its start marker to the end of
+// its end marker. Fails unless there's exactly one such chunk.
+fn find_note_chunk(contents: &str, note_id: usize) -> io::Result<(usize, usize)> {
+    let invalid = |msg: String| io::Error::new(io::ErrorKind::InvalidData, msg);
+
+    let start = match find_note_starts(contents, note_id).as_slice() {
+        [start] => *start,
+        [] => return Err(invalid(format!("Note {} not found in {}", note_id, FILE_PATH))),
+        _ => return Err(invalid(format!("Note {} appears more than once in {}", note_id, FILE_PATH))),
+    };
+
+    let end_marker = format!("(note.id{}.end)", note_id);
+    let end = marker_lines(&contents[start..], &end_marker)
+        .next()
+        .map(|(_, end)| start + end)
+        .ok_or_else(|| invalid(format!("Note {} has no end marker in {}", note_id, FILE_PATH)))?;
+
+    // A second note starting inside this one means its end marker went missing
+    let nested_start = contents[start..end].lines().skip(1).any(|line| {
+        let line = line.trim();
+        line.starts_with("(note.id") && line.ends_with(".start)")
+    });
+    if nested_start {
+        return Err(invalid(format!("Note {} is missing its end marker in {}", note_id, FILE_PATH)));
+    }
+
+    Ok((start, end))
+}
+
+// Where each start marker for a note is in the file
+fn find_note_starts(contents: &str, note_id: usize) -> Vec<usize> {
+    [
+        format!("(note.id{}.active.start)", note_id),
+        format!("(note.id{}.inactive.start)", note_id),
+    ]
+    .iter()
+    .flat_map(|marker| marker_lines(contents, marker).map(|(start, _)| start).collect::<Vec<_>>())
+    .collect()
+}
+
+// Byte ranges of the markers that have a line to themselves. Field values
+// share a line with their key, so marker text in a title or description
+// never counts.
+fn marker_lines<'a>(contents: &'a str, marker: &'a str) -> impl Iterator<Item = (usize, usize)> + 'a {
+    let mut offset = 0;
+    contents.split_inclusive('\n').filter_map(move |line| {
+        let line_start = offset;
+        offset += line.len();
+        let at = line_start + line.len() - line.trim_start().len();
+        (line.trim() == marker).then_some((at, at + marker.len()))
+    })
+}
+
+// ====== TRANSACTIONS =====
+/// A change to the store made while holding the lock on the metadata file, so
+/// two funknotes processes can't interleave. IDs are handed out and the notes
+/// rewritten in memory, and only saved on commit, so a change that fails part
+/// way doesn't burn any IDs or leave half its records behind.
+struct Transaction {
+    last_id: usize,          // The counter: last ID handed out
+    allocated: bool,
+    notes: Option<String>,   // The notes file as the change leaves it
+    _lock: LockFile,         // Released when the transaction is dropped
+}
+
+impl Transaction {
+    fn begin(meta_path: &Path) -> io::Result<Transaction> {
+        let lock = LockFile::acquire(meta_path)?;
+        let last_id = read_counter(&read_metadata(meta_path)?)?;
+
+        Ok(Transaction {
+            last_id,
+            allocated: false,
+            notes: None,
+            _lock: lock,
+        })
+    }
+
+    /// Hand out the next ID. Nothing is written until commit.
+    fn allocate_id(&mut self) -> usize {
+        self.last_id += 1;
+        self.allocated = true;
+        self.last_id
+    }
+}
+
+// The counter holds the last ID handed out. No counter yet means we start at 1.
+pub(crate) fn read_counter(meta: &Metadata) -> io::Result<usize> {
+    match meta_value(meta, "next.id") {
+        Some(v) => v.parse::<usize>().map_err(|_| std::io::Error::new(
             std::io::ErrorKind::InvalidData,
-            "Counter value out of range"
-        ))?;
-    
-    fs::write(METADATA_FILE, base_meta(value))?;  // ← Use ?
-    Ok(value_usize)
+            "Invalid counter format"
+        )),
+        None => Ok(0),
+    }
+}
+
+// An exclusive lock: a file next to the metadata file that only one process
+// can create. Removed again when dropped.
+pub(crate) struct LockFile {
+    path: PathBuf,
+}
+
+impl LockFile {
+    pub(crate) fn acquire(meta_path: &Path) -> io::Result<LockFile> {
+        let path = meta_path.with_extension("lock");
+        let started = Instant::now();
+
+        loop {
+            match OpenOptions::new().write(true).create_new(true).open(&path) {
+                Ok(mut file) => {
+                    // Just for whoever finds a lock lying around
+                    let _ = write!(file, "{}", std::process::id());
+                    return Ok(LockFile { path });
+                }
+                Err(e) if e.kind() == io::ErrorKind::AlreadyExists => {
+                    if lock_is_stale(&path) {
+                        let _ = fs::remove_file(&path);
+                        continue;
+                    }
+                    if started.elapsed() > LOCK_TIMEOUT {
+                        return Err(io::Error::new(
+                            io::ErrorKind::TimedOut,
+                            format!("Timed out waiting for {} (delete it if no funknotes is running)", path.display()),
+                        ));
+                    }
+                    thread::sleep(Duration::from_millis(5));
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+}
+
+impl Drop for LockFile {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn lock_is_stale(path: &Path) -> bool {
+    fs::metadata(path)
+        .and_then(|m| m.modified())
+        .ok()
+        .and_then(|modified| modified.elapsed().ok())
+        .is_some_and(|age| age > STALE_LOCK)
+}
+
+// ====== FORMAT VERSIONS =====
+// The notes file starts with a header line giving its format, e.g.
+// (funknotes.format=1). Files from before there was a header are version 0.
+struct Migration {
+    from: u32,                   // Upgrades from this version to the next
+    description: &'static str,
+    run: fn(&str) -> String,
+}
+
+// One entry per version bump, oldest first
+const MIGRATIONS: &[Migration] = &[
+    Migration {
+        from: 0,
+        description: "add the format header, write note end markers as (note.id{id}.end) \
+                      and drop the old '##### Milestones' placeholder",
+        run: migrate_v0,
+    },
+    Migration {
+        from: 1,
+        description: "escape backslashes in text and fold multi-line text onto its field's line",
+        run: migrate_v1,
+    },
+];
+
+fn render_header(version: u32) -> String {
+    format!("{}\n", base_meta("funknotes.format", &version.to_string()))
+}
+
+fn file_version(contents: &str) -> io::Result<u32> {
+    let header = contents.lines().next().unwrap_or_default().trim();
+    let Some(version) = header.strip_prefix("(funknotes.format=").and_then(|h| h.strip_suffix(')')) else {
+        return Ok(0); // No header yet
+    };
+
+    let version = version.parse::<u32>().map_err(|_| io::Error::new(
+        io::ErrorKind::InvalidData,
+        format!("Unreadable format header in {}: {}", FILE_PATH, header),
+    ))?;
+    if version > FORMAT_VERSION {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!("{} is format version {}, but this funknotes only reads up to {}. Time to upgrade",
+                FILE_PATH, version, FORMAT_VERSION),
+        ));
+    }
+    Ok(version)
+}
+
+// Bring file contents up to FORMAT_VERSION, returning the migrations that ran
+fn upgrade(contents: &str) -> io::Result<(String, Vec<&'static Migration>)> {
+    let from = file_version(contents)?;
+    let steps: Vec<&'static Migration> = MIGRATIONS.iter().filter(|m| m.from >= from).collect();
+
+    let mut contents = contents.to_string();
+    for migration in &steps {
+        contents = (migration.run)(&contents);
+    }
+    Ok((contents, steps))
+}
+
+fn migrate_v0(contents: &str) -> String {
+    let mut migrated = render_header(1);
+    for line in contents.lines() {
+        let trimmed = line.trim();
+        // Blanked rather than dropped, so every line keeps its number
+        if trimmed == "##### Milestones" {
+            migrated.push('\n');
+            continue;
+        }
+        match trimmed.strip_prefix("(note.id.").and_then(|l| l.strip_suffix(".end)")) {
+            Some(id) => migrated.push_str(&format!("(note.id{}.end)", id)),
+            None => migrated.push_str(line),
+        }
+        migrated.push('\n');
+    }
+    migrated
+}
+
+// Version 1 wrote text as is, so a line break in a description carried on
+// onto lines of its own. Those lines are joined back onto the field with \n
+// and left blank, again keeping line numbers.
+fn migrate_v1(contents: &str) -> String {
+    let mut lines: Vec<String> = vec![render_header(2).trim_end().to_string()];
+    let mut last_field: Option<usize> = None;
+
+    for line in contents.lines().skip(1) {
+        let trimmed = line.trim();
+        let is_marker = trimmed.starts_with('(') && trimmed.ends_with(')');
+
+        if let Some((path, value)) = split_v1_field(line) {
+            last_field = Some(lines.len());
+            lines.push(format!("{}:{}", path, escape(value)));
+        } else if trimmed.is_empty() || is_marker {
+            // Blank lines end a multi-line value as far as anyone can tell
+            last_field = None;
+            lines.push(line.to_string());
+        } else if let Some(i) = last_field {
+            lines[i].push_str(&escape(&format!("\n{}", line)));
+            lines.push(String::new());
+        } else {
+            lines.push(line.to_string());
+        }
+    }
+
+    let mut migrated = lines.join("\n");
+    migrated.push('\n');
+    migrated
+}
+
+// "    3-5.title: Text" -> ("    3-5.title", " Text")
+fn split_v1_field(line: &str) -> Option<(&str, &str)> {
+    let (path, value) = line.split_once(':')?;
+    let (prefix, _) = path.trim_start().split_once('.')?;
+    let is_prefix = !prefix.is_empty()
+        && prefix.starts_with(|c: char| c.is_ascii_digit())
+        && prefix.chars().all(|c| c.is_ascii_digit() || c == '-');
+    is_prefix.then_some((path, value))
+}
+
+// ====== JOURNAL =====
+// What a commit is about to write, saved first so a crash part way through can
+// be finished on the next run:
+//
+//   (journal.next.id=12)
+//   (journal.notes.bytes=345)
+//   ...the whole new notes file...
+//   (journal.write.offset=4096)
+//   (journal.write.bytes=80)
+//   ...bytes to put at that offset of the data file...
+//   (journal.end)
+//
+// The text store replaces its whole file; the binary store patches records in
+// place. Anything short of the end marker is a journal that was never finished.
+#[derive(Debug, Default, PartialEq)]
+pub(crate) struct Journal {
+    pub last_id: Option<usize>,          // New value for the counter
+    pub notes: Option<String>,           // New contents of the notes file
+    pub writes: Vec<(u64, Vec<u8>)>,     // Bytes to write at offsets in the data file
+}
+
+const JOURNAL_END: &str = "(journal.end)";
+
+pub(crate) fn render_journal(journal: &Journal) -> Vec<u8> {
+    fn line(rendered: &mut Vec<u8>, bytes: &[u8]) {
+        rendered.extend_from_slice(bytes);
+        rendered.push(b'\n');
+    }
+
+    let mut rendered = Vec::new();
+    if let Some(last_id) = journal.last_id {
+        line(&mut rendered, base_meta("journal.next.id", &last_id.to_string()).as_bytes());
+    }
+    if let Some(notes) = &journal.notes {
+        line(&mut rendered, base_meta("journal.notes.bytes", &notes.len().to_string()).as_bytes());
+        line(&mut rendered, notes.as_bytes());
+    }
+    for (offset, bytes) in &journal.writes {
+        line(&mut rendered, base_meta("journal.write.offset", &offset.to_string()).as_bytes());
+        line(&mut rendered, base_meta("journal.write.bytes", &bytes.len().to_string()).as_bytes());
+        line(&mut rendered, bytes);
+    }
+    line(&mut rendered, JOURNAL_END.as_bytes());
+    rendered
+}
+
+// None unless the journal is complete and makes sense
+pub(crate) fn parse_journal(contents: &[u8]) -> Option<Journal> {
+    let mut journal = Journal::default();
+    let mut offset = None;   // Of the write whose bytes come next
+    let mut rest = contents;
+
+    loop {
+        let end = rest.iter().position(|&b| b == b'\n')?;
+        let (line, after) = (std::str::from_utf8(&rest[..end]).ok()?, &rest[end + 1..]);
+        if line == JOURNAL_END {
+            return (after.is_empty() && offset.is_none()).then_some(journal);
+        }
+
+        // Notes and writes are taken by length, so they can hold anything
+        let (key, value) = line.strip_prefix('(')?.strip_suffix(')')?.split_once('=')?;
+        let take = |len: &str| -> Option<(&[u8], &[u8])> {
+            let len: usize = len.parse().ok()?;
+            Some((after.get(..len)?, after.get(len..)?.strip_prefix(b"\n")?))
+        };
+        rest = match key {
+            "journal.next.id" => {
+                journal.last_id = Some(value.parse().ok()?);
+                after
+            }
+            "journal.notes.bytes" => {
+                let (notes, after) = take(value)?;
+                journal.notes = Some(String::from_utf8(notes.to_vec()).ok()?);
+                after
+            }
+            "journal.write.offset" if offset.is_none() => {
+                offset = Some(value.parse().ok()?);
+                after
+            }
+            "journal.write.bytes" => {
+                let (bytes, after) = take(value)?;
+                journal.writes.push((offset.take()?, bytes.to_vec()));
+                after
+            }
+            _ => return None,
+        };
+    }
+}
+
+/// Write the journal, make the changes it holds, then drop it. A crash at any
+/// point leaves either the old files plus a journal that is discarded or
+/// replayed by recover_journal(), or the new files.
+pub(crate) fn apply_journal(journal_path: &Path, journal: &Journal, data_path: &Path, meta_path: &Path) -> io::Result<()> {
+    write_atomically(journal_path, render_journal(journal))?;
+    replay_journal(journal, data_path, meta_path)?;
+    fs::remove_file(journal_path)
+}
+
+// Writing the same journal twice leaves the same files, so a replay can be
+// cut short and run again
+fn replay_journal(journal: &Journal, data_path: &Path, meta_path: &Path) -> io::Result<()> {
+    if let Some(notes) = &journal.notes {
+        write_atomically(data_path, notes)?;
+    }
+    if !journal.writes.is_empty() {
+        let mut file = OpenOptions::new().write(true).open(data_path)?;
+        for (offset, bytes) in &journal.writes {
+            file.seek(SeekFrom::Start(*offset))?;
+            file.write_all(bytes)?;
+        }
+        file.sync_all()?;
+    }
+    if let Some(last_id) = journal.last_id {
+        let mut meta = read_metadata(meta_path)?;
+        set_meta_value(&mut meta, "next.id", Some(last_id.to_string()));
+        write_metadata(meta_path, &meta)?;
+    }
+    Ok(())
+}
+
+/// Deal with a journal left behind by a process that died mid-change. A
+/// complete one is replayed; a torn one means nothing was changed yet.
+pub(crate) fn recover_journal(journal_path: &Path, data_path: &Path, meta_path: &Path) -> io::Result<()> {
+    if !journal_path.exists() {
+        return Ok(());
+    }
+    // Whoever wrote it may still be at work
+    let _lock = LockFile::acquire(meta_path)?;
+    if !journal_path.exists() {
+        return Ok(());
+    }
+
+    match parse_journal(&fs::read(journal_path)?) {
+        Some(journal) => {
+            replay_journal(&journal, data_path, meta_path)?;
+            eprintln!("Note: finished saving a change that was interrupted last time.");
+        }
+        None => eprintln!("Warning: threw away a change that was interrupted before it was saved."),
+    }
+    fs::remove_file(journal_path)
+}
+
+// Write to a temporary file next to the real one, then rename it over the top,
+// so the real file is always either the old version or the new one
+pub(crate) fn write_atomically(path: &Path, contents: impl AsRef<[u8]>) -> io::Result<()> {
+    let mut temp_name = path.file_name().unwrap_or_default().to_os_string();
+    temp_name.push(".tmp");
+    let temp_path = path.with_file_name(temp_name);
+
+    let mut file = fs::File::create(&temp_path)?;
+    file.write_all(contents.as_ref())?;
+    file.sync_all()?;
+    fs::rename(&temp_path, path)
+}
+
+// ====== METADATA =====
+// The metadata file is a list of (key=value) lines, e.g. (next.id=12)
+pub(crate) type Metadata = Vec<(String, String)>;
+
+pub(crate) fn read_metadata(meta_path: &Path) -> io::Result<Metadata> {
+    if !meta_path.exists() {
+        return Ok(Vec::new());
+    }
+
+    let contents = fs::read_to_string(meta_path)?;
+    Ok(contents
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.trim().strip_prefix('(')?.strip_suffix(')')?.split_once('=')?;
+            Some((key.to_string(), value.to_string()))
+        })
+        .collect())
+}
+
+pub(crate) fn write_metadata(meta_path: &Path, meta: &Metadata) -> io::Result<()> {
+    let lines: Vec<String> = meta.iter().map(|(key, value)| base_meta(key, value)).collect();
+    write_atomically(meta_path, lines.join("\n"))
+}
+
+pub(crate) fn meta_value<'a>(meta: &'a Metadata, key: &str) -> Option<&'a str> {
+    meta.iter().find(|(k, _)| k == key).map(|(_, v)| v.as_str())
+}
+
+fn base_location(location: &Location) -> String {
+    let part = |id: Option<usize>| id.map_or("-".to_string(), |id| id.to_string());
+    format!("{}/{}", part(location.project_id), part(location.object_id))
+}
+
+fn parse_location(text: &str) -> Option<Location> {
+    let part = |s: &str| if s == "-" { Ok(None) } else { s.parse::<usize>().map(Some) };
+    let (project, object) = text.split_once('/')?;
+    Some(Location {
+        project_id: part(project).ok()?,
+        object_id: part(object).ok()?,
+    })
+}
+
+// Set or replace a value. None removes the key altogether.
+pub(crate) fn set_meta_value(meta: &mut Metadata, key: &str, value: Option<String>) {
+    match (meta.iter().position(|(k, _)| k == key), value) {
+        (Some(i), Some(value)) => meta[i].1 = value,
+        (None, Some(value)) => meta.push((key.to_string(), value)),
+        (Some(i), None) => {
+            meta.remove(i);
+        }
+        (None, None) => {}
+    }
+}
+
+/// Read the saved navigation state (which project and object are current)
+/// from the metadata file.
+pub(crate) fn read_state(meta_path: &Path) -> io::Result<FunkState> {
+    let meta = read_metadata(meta_path)?;
+    let id = |key: &str| meta_value(&meta, key).and_then(|v| v.parse::<usize>().ok());
+
+    // History is saved as project/object pairs, with "-" for nothing selected
+    let history: Vec<Location> = meta_value(&meta, "history")
+        .map(|v| v.split(',').filter_map(parse_location).collect())
+        .unwrap_or_default();
+
+    Ok(FunkState {
+        current_project_id: id("current.project"),
+        current_object_id: id("current.object"),
+        history_pos: id("history.pos").unwrap_or(0).min(history.len().saturating_sub(1)),
+        history,
+        primary_project_id: id("primary"),
+    })
+}
+
+/// Save the navigation state, leaving the rest of the metadata alone.
+/// The file is only rewritten when something actually changed.
+pub(crate) fn write_state(meta_path: &Path, state: &FunkState) -> io::Result<()> {
+    let _lock = LockFile::acquire(meta_path)?;
+    let mut meta = read_metadata(meta_path)?;
+    let before = meta.clone();
+
+    set_meta_value(&mut meta, "current.project", state.current_project_id.map(|id| id.to_string()));
+    set_meta_value(&mut meta, "current.object", state.current_object_id.map(|id| id.to_string()));
+
+    let history: Vec<String> = state.history.iter().map(base_location).collect();
+    let has_history = !history.is_empty();
+    set_meta_value(&mut meta, "history", has_history.then(|| history.join(",")));
+    set_meta_value(&mut meta, "history.pos", has_history.then(|| state.history_pos.to_string()));
+    set_meta_value(&mut meta, "primary", state.primary_project_id.map(|id| id.to_string()));
+
+    if meta == before {
+        return Ok(());
+    }
+    write_metadata(meta_path, &meta)
+}
+
+// ====== RENDERING =====
+/// Render every note (and everything hanging off it) in file format,
+/// followed by the trash.
+pub fn render_notebook(notebook: &Notebook) -> String {
+    let mut rendered = render_header(FORMAT_VERSION);
+    for note in &notebook.notes {
+        rendered.push_str(&base_note(note, notebook));
+    }
+
+    for entry in &notebook.trash {
+        rendered.push_str(&base_trash(entry, notebook));
+    }
+    rendered
 }
 
-fn base_note(note: &Funknote) -> String {
+fn base_note(note: &Funknote, notebook: &Notebook) -> String {
     // First build a local set of variables to fill the format string
-    let id = get_next_id().unwrap_or(0);
+    let id = note.id;
     let status = if note.active { "active" } else { "inactive" };
-    let title = &note.title;
-    let description = &note.description;
+    let title = escape(&note.title);
+    let description = escape(&note.description);
     let created_on = note.created_on;
 
+    // Children are looked up in the notebook by the IDs the note holds
+    let mut children = String::new();
+    for object in notebook.objects.iter().filter(|o| note.objects.contains(&o.id)) {
+        children.push_str(&base_object(id, object, notebook));
+    }
+    for milestone in notebook.milestones.iter().filter(|m| note.milestones.contains(&m.id)) {
+        children.push_str(&base_milestone(id, milestone));
+    }
+
     // Using a raw string literal - no escaping needed
    format!( r#"
 (note.id{id}.{status}.start)
@@ -68,119 +913,1029 @@ fn base_note(note: &Funknote) -> String {
 {id}.description: {description}
 
 {id}.date: {created_on}
+{children}
+(note.id{id}.end)
+"#)
+}
 
-    ##### Milestones
+fn base_object(note_id: usize, object: &Object, notebook: &Notebook) -> String {
+    let id = object.id;
+    let status = if object.active { "active" } else { "inactive" };
+    let title = escape(&object.title);
+    let description = escape(&object.description);
+    let created_on = object.created_on;
+
+    let mut items = String::new();
+    for item in notebook.items.iter().filter(|i| object.items.contains(&i.id)) {
+        items.push_str(&base_item(note_id, id, item));
+    }
+
+   format!( r#"
+    (object.id{id}.{status}.start)
+
+        {note_id}-{id}.title: {title}
+
+        {note_id}-{id}.description: {description}
 
-(note.id.{id}.end)
+        {note_id}-{id}.date: {created_on}
+{items}
+    (object.id{id}.end)
 "#)
 }
 
-fn base_meta(id: i64) -> String {
-    // Using a raw string literal - no escaping needed
-    // Need to put in identifier for 'primary note' attribute
-   format!( r#"(next.id={id})"#)
+fn base_item(note_id: usize, object_id: usize, item: &Item) -> String {
+    let id = item.id;
+    let text = escape(&item.text);
+    let created_on = item.created_on;
+    let completed = item.completed;
+    let completed_on = optional_date(item.completed_on);
+
+   format!( r#"
+        (item.id{id}.start)
+
+            {note_id}-{object_id}-{id}.text: {text}
+
+            {note_id}-{object_id}-{id}.date: {created_on}
+
+            {note_id}-{object_id}-{id}.completed: {completed}
+
+            {note_id}-{object_id}-{id}.completed_on: {completed_on}
+
+        (item.id{id}.end)
+"#)
 }
 
-fn append_to_file(file_path: &str, content: &str) -> io::Result<()> {
-    let mut file = OpenOptions::new()
-        .create(true)   // Creates if doesn't exist
-        .append(true)   // Appends if does exist
-        .open(file_path)?;
-    
-    file.write_all(content.as_bytes())?;
-    Ok(())
+fn base_milestone(note_id: usize, milestone: &Milestone) -> String {
+    let id = milestone.id;
+    let title = escape(&milestone.title);
+    let description = escape(&milestone.description);
+    let target_date = milestone.target_date;
+    let target = match milestone.target {
+        MilestoneTarget::Project(t) => format!("project.{t}"),
+        MilestoneTarget::Object(t) => format!("object.{t}"),
+        MilestoneTarget::Item(t) => format!("item.{t}"),
+    };
+    let completed = milestone.completed;
+    let completed_on = optional_date(milestone.completed_on);
+
+   format!( r#"
+    (milestone.id{id}.start)
+
+        {note_id}-{id}.title: {title}
+
+        {note_id}-{id}.description: {description}
+
+        {note_id}-{id}.date: {target_date}
+
+        {note_id}-{id}.target: {target}
+
+        {note_id}-{id}.completed: {completed}
+
+        {note_id}-{id}.completed_on: {completed_on}
+
+    (milestone.id{id}.end)
+"#)
 }
-fn read_from_file(file_path: &str) -> io::Result<String> {
+
+fn base_trash(entry: &TrashEntry, notebook: &Notebook) -> String {
+    let kind = entry.kind.name();
+    let id = entry.id;
+    let deleted_on = entry.deleted_on;
+    let contents = &entry.contents;
+
+    // Whole notes carry their children. Anything whose parent wasn't deleted
+    // with it is written on its own, with the parent's ID in its field prefix.
+    let mut records: String = contents
+        .notes
+        .iter()
+        .map(|note| base_note(note, contents))
+        .collect();
+    for object in contents.objects.iter().filter(|o| !contents.notes.iter().any(|n| n.objects.contains(&o.id))) {
+        records.push_str(&base_object(object.project_id, object, contents));
+    }
+    for item in contents.items.iter().filter(|i| !contents.objects.iter().any(|o| o.items.contains(&i.id))) {
+        let note_id = project_of_object(notebook, item.object_id);
+        records.push_str(&base_item(note_id, item.object_id, item));
+    }
+    for milestone in contents.milestones.iter().filter(|m| !contents.notes.iter().any(|n| n.milestones.contains(&m.id))) {
+        records.push_str(&base_milestone(milestone.project_id, milestone));
+    }
+
+   format!( r#"
+(trash.{kind}.id{id}.{deleted_on}.start)
+{records}
+(trash.{kind}.id{id}.end)
+"#)
+}
+
+// Which project an object belongs to, looking in the trash too. 0 if unknown.
+fn project_of_object(notebook: &Notebook, object_id: usize) -> usize {
+    notebook
+        .objects
+        .iter()
+        .chain(notebook.trash.iter().flat_map(|e| e.contents.objects.iter()))
+        .find(|o| o.id == object_id)
+        .map(|o| o.project_id)
+        .unwrap_or(0)
+}
+
+fn optional_date(ts: Option<u64>) -> String {
+    match ts {
+        Some(ts) => ts.to_string(),
+        None => "none".to_string(),
+    }
+}
+
+fn base_meta(key: &str, value: &str) -> String {
+    // Using a raw string literal - no escaping needed
+   format!( r#"({key}={value})"#)
+}
+
+fn read_from_file(file_path: &Path) -> io::Result<String> {
     fs::read_to_string(file_path)
 }
 
-/// Parse a single note chunk into a Funknote struct
-fn parse_note_chunk(chunk: &str, id: usize) -> Result<Funknote, String> {
-    // Split the chunk into lines for parsing
-    let lines: Vec<&str> = chunk.lines().collect();
-    
-    let mut title = String::new();
-    let mut description = String::new();
-    let mut created_on: u64 = 0;
-    let mut active = true;
-    
-    // Parse the status from the first line if it exists
-    if let Some(first_line) = lines.first() {
-        if first_line.contains("inactive") {
-            active = false;
-        }
-    }
-    
-    // Parse each line looking for our fields
-    for line in lines {
-        let line = line.trim();
-        
-        if line.contains(".title:") {
-            title = line.split_once(".title:")
-                .map(|(_, t)| t.trim().to_string())
-                .unwrap_or_default();
-        } else if line.contains(".description:") {
-            description = line.split_once(".description:")
-                .map(|(_, d)| d.trim().to_string())
-                .unwrap_or_default();
-        } else if line.contains(".date:") {
-            created_on = line.split_once(".date:")
-                .and_then(|(_, d)| d.trim().parse::<u64>().ok())
-                .unwrap_or(0);
-        }
-    }
-    
-    // Validate we got the essential fields
-    if title.is_empty() {
-        return Err(format!("Note {} missing title", id));
-    }
-    
-    Ok(Funknote {
-        id,
-        title,
-        description,
-        created_on,
-        active,
-        objects: Vec::new(), // NOTE: Filled with empty Vec, update as needed
-        milestones: Vec::new(),
-    })
+// ====== PARSING =====
+/// Something wrong in the notes file, and where. Lines and columns count from 1.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ParseError {
+    pub line: usize,
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for ParseError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "line {}, column {}: {}", self.line, self.column, self.message)
+    }
+}
+
+// What a block marker opens or closes
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum MarkerKind {
+    Record(NodeKind),
+    Trash(NodeKind),   // A trash entry for a deleted record of this kind
+}
+
+impl MarkerKind {
+    // As written in the file: notes are "note", trash entries "trash.{kind}"
+    fn name(&self) -> String {
+        match self {
+            MarkerKind::Record(NodeKind::Project) => "note".to_string(),
+            MarkerKind::Record(kind) => kind.name().to_string(),
+            MarkerKind::Trash(kind) => format!("trash.{}", kind.name()),
+        }
+    }
+}
+
+// A block marker line, e.g. (object.id5.active.start) or (trash.item.id9.end)
+struct Marker {
+    kind: MarkerKind,
+    id: usize,
+    start: bool,
+    active: bool,      // Notes and objects only
+    deleted_on: u64,   // Trash entries only
+}
+
+// A block that has started but not ended yet
+struct Open {
+    kind: MarkerKind,
+    id: usize,
+    index: usize,   // Where its record is in the notebook's Vec (or in the trash)
+    line: usize,    // Where it started
+}
+
+/// Parse the contents of the notes file back into a Notebook, or list
+/// everything wrong with it. Children are attached to whichever block
+/// encloses them.
+pub fn parse_notebook(contents: &str) -> Result<Notebook, Vec<ParseError>> {
+    let mut parser = Parser {
+        notebook: Notebook::default(),
+        open: Vec::new(),
+        errors: Vec::new(),
+    };
+
+    for (i, line) in contents.lines().enumerate() {
+        parser.line(i + 1, line.strip_suffix('\r').unwrap_or(line));
+    }
+
+    // Anything still open lost its end marker
+    for open in std::mem::take(&mut parser.open) {
+        parser.errors.push(ParseError {
+            line: open.line,
+            column: 1,
+            message: format!("The {} with ID {} is never closed", open.kind.name(), open.id),
+        });
+    }
+
+    if parser.errors.is_empty() {
+        Ok(parser.notebook)
+    } else {
+        parser.errors.sort_by_key(|e| (e.line, e.column));
+        Err(parser.errors)
+    }
+}
+
+struct Parser {
+    notebook: Notebook,
+    open: Vec<Open>,
+    errors: Vec<ParseError>,
+}
+
+impl Parser {
+    fn line(&mut self, line_no: usize, line: &str) {
+        let trimmed = line.trim();
+        let indent = line.len() - line.trim_start().len();
+        let column = |offset: usize| line[..offset].chars().count() + 1;
+
+        // The header has already been checked by upgrade()
+        let is_header = line_no == 1 && trimmed.starts_with("(funknotes.format=");
+        let result = if trimmed.is_empty() || is_header {
+            Ok(())
+        } else if let Some(marker) = trimmed.strip_prefix('(').and_then(|l| l.strip_suffix(')')) {
+            parse_marker(marker)
+                .and_then(|marker| if marker.start { self.start(&marker, line_no) } else { self.end(&marker, line_no) })
+                .map_err(|message| (indent, message))
+        } else if trimmed.starts_with(|c: char| c.is_ascii_digit()) {
+            self.field(&line[indent..]).map_err(|(offset, message)| (indent + offset, message))
+        } else {
+            Err((indent, format!("Expected a block marker or a field, found '{}'", trimmed)))
+        };
+
+        if let Err((offset, message)) = result {
+            self.errors.push(ParseError { line: line_no, column: column(offset), message });
+        }
+    }
+
+    // Records inside a trash entry go into the entry rather than the notebook
+    fn target(&mut self) -> &mut Notebook {
+        let in_trash = matches!(self.open.first(), Some(Open { kind: MarkerKind::Trash(_), .. }));
+        match self.notebook.trash.len() {
+            n if in_trash && n > 0 => &mut self.notebook.trash[n - 1].contents,
+            _ => &mut self.notebook,
+        }
     }
 
-/// Read all notes from the file and return them as a vector
-pub fn list_all_notes() -> io::Result<Vec<Funknote>> {
-    // Check if file exists first
-    if !Path::new(FILE_PATH).exists() {
-        return Ok(Vec::new()); // Return empty vector if no notes exist yet
-    }
-    
-    let contents = fs::read_to_string(FILE_PATH)?;
-    
-    // Split by the delimiter and collect into funknotes
-    let notes: Vec<Funknote> = contents
-        .split(SPLIT_CODE)
-        .enumerate()
-        .skip(1) // Skip first empty chunk before first delimiter
-        .filter_map(|(idx, chunk)| {
-            // Extract the ID from the chunk itself
-            let id = chunk
-                .lines()
-                .find(|line| line.contains(".title:"))
-                .and_then(|line| {
-                    line.split('.')
-                        .next()
-                        .and_then(|s| s.trim().parse::<usize>().ok())
-                })?;
-            
-            // Parse the chunk, log errors but continue
-            match parse_note_chunk(chunk, id) {
-                Ok(note) => Some(note),
-                Err(e) => {
-                    eprintln!("Warning: Failed to parse note: {}", e);
-                    None
+    fn start(&mut self, marker: &Marker, line_no: usize) -> Result<(), String> {
+        let id = marker.id;
+        let parent = self.open.last().map(|o| (o.kind, o.index));
+        // Records deleted without their parent stand alone in the trash
+        let orphan = matches!(parent, Some((MarkerKind::Trash(_), _)));
+        let misplaced = |what: &str, place: &str| Err(format!("{} has to be {}", what, place));
+
+        let index = match marker.kind {
+            MarkerKind::Trash(kind) => {
+                if parent.is_some() {
+                    return misplaced("A trash entry", "outside every other block");
                 }
+                self.notebook.trash.push(TrashEntry {
+                    kind,
+                    id,
+                    deleted_on: marker.deleted_on,
+                    contents: Notebook::default(),
+                });
+                self.notebook.trash.len() - 1
             }
-        })
-        .collect();
-    
-    Ok(notes)
+            MarkerKind::Record(NodeKind::Project) => {
+                if parent.is_some() && !orphan {
+                    return misplaced("A note", "outside every other block");
+                }
+                let notes = &mut self.target().notes;
+                if notes.iter().any(|n| n.id == id) {
+                    return Err(format!("There's already a note with ID {}", id));
+                }
+                notes.push(Funknote {
+                    id,
+                    title: String::new(),
+                    description: String::new(),
+                    created_on: 0,
+                    active: marker.active,
+                    objects: Vec::new(),
+                    milestones: Vec::new(),
+                });
+                notes.len() - 1
+            }
+            MarkerKind::Record(NodeKind::Object) => {
+                let project_id = match parent {
+                    Some((MarkerKind::Record(NodeKind::Project), i)) => {
+                        let note = &mut self.target().notes[i];
+                        note.objects.push(id);
+                        note.id
+                    }
+                    _ if orphan => 0, // Filled in from the field prefix
+                    _ => return misplaced("An object", "inside a note"),
+                };
+                let objects = &mut self.target().objects;
+                if objects.iter().any(|o| o.id == id) {
+                    return Err(format!("There's already an object with ID {}", id));
+                }
+                objects.push(Object {
+                    id,
+                    project_id,
+                    title: String::new(),
+                    description: String::new(),
+                    created_on: 0,
+                    active: marker.active,
+                    items: Vec::new(),
+                });
+                objects.len() - 1
+            }
+            MarkerKind::Record(NodeKind::Item) => {
+                let object_id = match parent {
+                    Some((MarkerKind::Record(NodeKind::Object), i)) => {
+                        let object = &mut self.target().objects[i];
+                        object.items.push(id);
+                        object.id
+                    }
+                    _ if orphan => 0,
+                    _ => return misplaced("An item", "inside an object"),
+                };
+                let items = &mut self.target().items;
+                if items.iter().any(|i| i.id == id) {
+                    return Err(format!("There's already an item with ID {}", id));
+                }
+                items.push(Item {
+                    id,
+                    object_id,
+                    text: String::new(),
+                    created_on: 0,
+                    completed: false,
+                    completed_on: None,
+                });
+                items.len() - 1
+            }
+            MarkerKind::Record(NodeKind::Milestone) => {
+                let project_id = match parent {
+                    Some((MarkerKind::Record(NodeKind::Project), i)) => {
+                        let note = &mut self.target().notes[i];
+                        note.milestones.push(id);
+                        note.id
+                    }
+                    _ if orphan => 0,
+                    _ => return misplaced("A milestone", "inside a note"),
+                };
+                let milestones = &mut self.target().milestones;
+                if milestones.iter().any(|m| m.id == id) {
+                    return Err(format!("There's already a milestone with ID {}", id));
+                }
+                milestones.push(Milestone {
+                    id,
+                    project_id,
+                    title: String::new(),
+                    description: String::new(),
+                    target_date: 0,
+                    completed: false,
+                    completed_on: None,
+                    target: MilestoneTarget::Project(project_id),
+                });
+                milestones.len() - 1
+            }
+        };
+
+        self.open.push(Open { kind: marker.kind, id, index, line: line_no });
+        Ok(())
+    }
+
+    fn end(&mut self, marker: &Marker, line_no: usize) -> Result<(), String> {
+        let Some(position) = self.open.iter().rposition(|o| o.kind == marker.kind && o.id == marker.id) else {
+            return Err(format!("There's no {} with ID {} open to end here", marker.kind.name(), marker.id));
+        };
+
+        // Blocks opened inside this one should have ended first
+        for open in self.open.drain(position + 1..) {
+            self.errors.push(ParseError {
+                line: open.line,
+                column: 1,
+                message: format!(
+                    "The {} with ID {} isn't closed before line {}",
+                    open.kind.name(), open.id, line_no
+                ),
+            });
+        }
+        self.open.pop();
+        Ok(())
+    }
+
+    /// Apply a `{path}.{key}: {value}` line to the record that's currently open.
+    /// Errors come with the byte offset in the line they're about.
+    fn field(&mut self, line: &str) -> Result<(), (usize, String)> {
+        let (path, value) = line
+            .split_once(':')
+            .ok_or((0, "Expected a field like 3.title: value".to_string()))?;
+        let (prefix, key) = path
+            .split_once('.')
+            .ok_or((0, "Expected a field like 3.title: value".to_string()))?;
+        let ids: Vec<usize> = prefix
+            .split('-')
+            .map(|id| id.parse::<usize>())
+            .collect::<Result<_, _>>()
+            .map_err(|_| (0, format!("Expected IDs separated by '-', found '{}'", prefix)))?;
+
+        // The value starts after ": "
+        let value_offset = path.len() + 1 + usize::from(value.starts_with(' '));
+        let value = value.strip_prefix(' ').unwrap_or(value);
+        let at_value = |message: String| (value_offset, message);
+        let key_offset = prefix.len() + 1;
+
+        let Some(open) = self.open.last() else {
+            return Err((0, "This field isn't inside any record".to_string()));
+        };
+        let (kind, index, id) = match open.kind {
+            MarkerKind::Record(kind) => (kind, open.index, open.id),
+            MarkerKind::Trash(_) => return Err((0, "This field isn't inside any record".to_string())),
+        };
+        let parent_id = match self.open.iter().rev().nth(1) {
+            Some(Open { kind: MarkerKind::Record(_), id, .. }) => Some(*id),
+            _ => None, // An orphan in the trash, or a note
+        };
+
+        // The prefix is the chain of IDs down to the record, e.g. "3-5-9" for an item
+        let depth = match kind {
+            NodeKind::Project => 1,
+            NodeKind::Object | NodeKind::Milestone => 2,
+            NodeKind::Item => 3,
+        };
+        let prefix_parent = ids.len().checked_sub(2).map(|i| ids[i]);
+        if ids.len() != depth || ids.last() != Some(&id) {
+            return Err((0, format!("This field is for {}, but the open {} has ID {}", prefix, kind.name(), id)));
+        }
+        if parent_id.is_some() && prefix_parent != parent_id {
+            return Err((0, format!(
+                "The {} is inside {}, but this field says its parent is {}",
+                kind.name(), parent_id.unwrap_or(0), prefix_parent.unwrap_or(0)
+            )));
+        }
+        let parent = prefix_parent.unwrap_or(0);
+
+        let text = || unescape(value).map_err(|(offset, message)| (value_offset + offset, message));
+        let number = || value.parse::<u64>().map_err(|_| at_value(format!("Expected a number, found '{}'", value)));
+        let flag = || match value {
+            "true" => Ok(true),
+            "false" => Ok(false),
+            _ => Err(at_value(format!("Expected true or false, found '{}'", value))),
+        };
+        let date = || match value {
+            "none" => Ok(None),
+            _ => number().map(Some),
+        };
+        let unknown = || Err((key_offset, format!("A {} has no field '{}'", kind.name(), key)));
+
+        let notebook = self.target();
+        match kind {
+            NodeKind::Project => {
+                let note = &mut notebook.notes[index];
+                match key {
+                    "title" => note.title = text()?,
+                    "description" => note.description = text()?,
+                    "date" => note.created_on = number()?,
+                    _ => return unknown(),
+                }
+            }
+            NodeKind::Object => {
+                let object = &mut notebook.objects[index];
+                if object.project_id == 0 {
+                    object.project_id = parent;
+                }
+                match key {
+                    "title" => object.title = text()?,
+                    "description" => object.description = text()?,
+                    "date" => object.created_on = number()?,
+                    _ => return unknown(),
+                }
+            }
+            NodeKind::Item => {
+                let item = &mut notebook.items[index];
+                if item.object_id == 0 {
+                    item.object_id = parent;
+                }
+                match key {
+                    "text" => item.text = text()?,
+                    "date" => item.created_on = number()?,
+                    "completed" => item.completed = flag()?,
+                    "completed_on" => item.completed_on = date()?,
+                    _ => return unknown(),
+                }
+            }
+            NodeKind::Milestone => {
+                let milestone = &mut notebook.milestones[index];
+                if milestone.project_id == 0 {
+                    milestone.project_id = parent;
+                }
+                match key {
+                    "title" => milestone.title = text()?,
+                    "description" => milestone.description = text()?,
+                    "date" => milestone.target_date = number()?,
+                    "target" => {
+                        milestone.target = parse_target(value).ok_or_else(|| at_value(format!(
+                            "Expected a target like project.3, object.5 or item.9, found '{}'", value
+                        )))?;
+                    }
+                    "completed" => milestone.completed = flag()?,
+                    "completed_on" => milestone.completed_on = date()?,
+                    _ => return unknown(),
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn parse_marker(marker: &str) -> Result<Marker, String> {
+    let parts: Vec<&str> = marker.split('.').collect();
+    let (kind, rest) = match parts.as_slice() {
+        ["trash", kind, rest @ ..] => {
+            let kind = NodeKind::from_name(kind).ok_or_else(|| format!("Unknown kind of trash entry '{}'", kind))?;
+            (MarkerKind::Trash(kind), rest)
+        }
+        ["note", rest @ ..] => (MarkerKind::Record(NodeKind::Project), rest),
+        [name, rest @ ..] => match NodeKind::from_name(name) {
+            Some(NodeKind::Project) | None => return Err(format!("Unknown kind of block '{}'", name)),
+            Some(kind) => (MarkerKind::Record(kind), rest),
+        },
+        [] => unreachable!("split always gives at least one part"),
+    };
+
+    let (id, rest) = rest.split_first().ok_or_else(|| format!("The {} marker has no ID", kind.name()))?;
+    let id = id
+        .strip_prefix("id")
+        .and_then(|n| n.parse::<usize>().ok())
+        .ok_or_else(|| format!("Expected an ID like id12, found '{}'", id))?;
+
+    let mut parsed = Marker { kind, id, start: true, active: true, deleted_on: 0 };
+    match (kind, rest) {
+        (_, ["end"]) => parsed.start = false,
+        (MarkerKind::Record(NodeKind::Project | NodeKind::Object), [status, "start"]) => {
+            parsed.active = match *status {
+                "active" => true,
+                "inactive" => false,
+                _ => return Err(format!("Expected active or inactive, found '{}'", status)),
+            };
+        }
+        (MarkerKind::Record(NodeKind::Item | NodeKind::Milestone), ["start"]) => {}
+        (MarkerKind::Trash(_), [deleted_on, "start"]) => {
+            parsed.deleted_on = deleted_on
+                .parse::<u64>()
+                .map_err(|_| format!("Expected when the entry was deleted, found '{}'", deleted_on))?;
+        }
+        _ => return Err(format!("Not a valid {} start or end marker", kind.name())),
+    }
+    Ok(parsed)
+}
+
+fn parse_target(value: &str) -> Option<MilestoneTarget> {
+    let (kind, id) = value.split_once('.')?;
+    let id = id.parse::<usize>().ok()?;
+    match kind {
+        "project" => Some(MilestoneTarget::Project(id)),
+        "object" => Some(MilestoneTarget::Object(id)),
+        "item" => Some(MilestoneTarget::Item(id)),
+        _ => None,
+    }
+}
+
+// Text is written on one line: backslashes, line breaks and tabs are escaped
+fn escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '\n' => escaped.push_str("\\n"),
+            '\r' => escaped.push_str("\\r"),
+            '\t' => escaped.push_str("\\t"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+// Errors give the byte offset of the bad escape
+fn unescape(text: &str) -> Result<String, (usize, String)> {
+    let mut unescaped = String::with_capacity(text.len());
+    let mut chars = text.char_indices();
+
+    while let Some((i, c)) = chars.next() {
+        if c != '\\' {
+            unescaped.push(c);
+            continue;
+        }
+        match chars.next() {
+            Some((_, '\\')) => unescaped.push('\\'),
+            Some((_, 'n')) => unescaped.push('\n'),
+            Some((_, 'r')) => unescaped.push('\r'),
+            Some((_, 't')) => unescaped.push('\t'),
+            Some((_, other)) => return Err((i, format!("Unknown escape '\\{}'. Write \\\\ for a backslash", other))),
+            None => return Err((i, "The line ends in a lone backslash. Write \\\\ for a backslash".to_string())),
+        }
+    }
+    Ok(unescaped)
+}
+
+// ================== TESTS ===================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::fixtures::{sample_notebook, sample_trash, temp_dir};
+
+    #[test]
+    fn test_notebook_round_trip() {
+        let mut notebook = sample_notebook();
+        notebook.notes[0].description = "Veggie patch: spring".to_string();
+        notebook.trash.push(sample_trash());
+        let rendered = render_notebook(&notebook);
+        assert_eq!(parse_notebook(&rendered), Ok(notebook));
+    }
+
+    #[test]
+    fn test_replace_note_chunk_only_touches_that_note() {
+        let mut notebook = sample_notebook();
+        notebook.notes.push(Funknote {
+            id: 8,
+            title: "Shed".to_string(),
+            description: String::new(),
+            created_on: 1764633600,
+            active: true,
+            objects: Vec::new(),
+            milestones: Vec::new(),
+        });
+        let contents = render_notebook(&notebook);
+
+        notebook.objects[0].title = "Raised beds".to_string();
+        let updated = replace_note_chunk(&contents, &notebook, 1).unwrap();
+
+        assert_eq!(parse_notebook(&updated).as_ref(), Ok(&notebook));
+        let shed_chunk = &contents[contents.find("(note.id8.").unwrap()..];
+        assert!(updated.ends_with(shed_chunk));
+        assert!(replace_note_chunk(&contents, &notebook, 99).is_err());
+    }
+
+    #[test]
+    fn test_concurrent_id_allocation() {
+        let dir = temp_dir("concurrent_ids");
+
+        let workers: Vec<_> = (0..8)
+            .map(|_| {
+                let dir = dir.clone();
+                thread::spawn(move || {
+                    let mut store = TextStore::open(&dir).unwrap();
+                    (0..25)
+                        .map(|_| {
+                            store.begin().unwrap();
+                            let id = store.allocate_id().unwrap();
+                            store.commit().unwrap();
+                            id
+                        })
+                        .collect::<Vec<usize>>()
+                })
+            })
+            .collect();
+
+        let mut ids: Vec<usize> = workers.into_iter().flat_map(|w| w.join().unwrap()).collect();
+        ids.sort();
+        assert_eq!(ids, (1..=200).collect::<Vec<usize>>());
+        assert!(!dir.join(METADATA_FILE).with_extension("lock").exists());
+    }
+
+    #[test]
+    fn test_uncommitted_ids_are_not_burned() {
+        let mut store = TextStore::open(&temp_dir("unburned_ids")).unwrap();
+
+        store.begin().unwrap();
+        assert_eq!(store.allocate_id().unwrap(), 1);
+        store.abort(); // e.g. the note failed to write
+
+        store.begin().unwrap();
+        assert_eq!(store.allocate_id().unwrap(), 1);
+        assert_eq!(store.allocate_id().unwrap(), 2);
+        store.commit().unwrap();
+
+        store.begin().unwrap();
+        assert_eq!(store.allocate_id().unwrap(), 3);
+    }
+
+    #[test]
+    fn test_aborted_change_writes_nothing() {
+        let dir = temp_dir("aborted_change");
+        let mut store = TextStore::open(&dir).unwrap();
+        let note = sample_notebook().notes[0].clone();
+
+        store.begin().unwrap();
+        store.save_project(&Funknote { objects: Vec::new(), milestones: Vec::new(), ..note }).unwrap();
+        assert_eq!(store.load_notebook().unwrap().notes.len(), 1); // Seen inside the change
+        store.abort();
+
+        assert!(store.load_notebook().unwrap().notes.is_empty());
+        assert!(!dir.join(FILE_PATH).exists());
+    }
+
+    #[test]
+    fn test_journal_round_trip() {
+        let journal = Journal {
+            last_id: Some(12),
+            notes: Some(render_notebook(&sample_notebook()) + "(journal.end)\n"),
+            writes: vec![(16, b"\n(journal.end)\n\xff".to_vec()), (4096, Vec::new())],
+        };
+        let rendered = render_journal(&journal);
+        assert_eq!(parse_journal(&rendered), Some(journal));
+
+        // However far a write got before a crash, a torn journal is never mistaken for a whole one
+        for len in 0..rendered.len() {
+            assert_eq!(parse_journal(&rendered[..len]), None, "cut at {}", len);
+        }
+    }
+
+    #[test]
+    fn test_interrupted_commit_is_replayed() {
+        let dir = temp_dir("replay_journal");
+        fs::write(dir.join(FILE_PATH), "(old contents)").unwrap();
+        fs::write(dir.join(METADATA_FILE), "(next.id=4)\n(primary=1)").unwrap();
+
+        // Died after the journal was saved, part way through writing the notes
+        let notes = render_notebook(&sample_notebook());
+        let journal = Journal { last_id: Some(6), notes: Some(notes.clone()), ..Journal::default() };
+        fs::write(dir.join(JOURNAL_FILE), render_journal(&journal)).unwrap();
+        fs::write(dir.join(format!("{}.tmp", FILE_PATH)), &notes[..notes.len() / 2]).unwrap();
+
+        let mut store = TextStore::open(&dir).unwrap();
+        assert_eq!(fs::read_to_string(dir.join(FILE_PATH)).unwrap(), notes);
+        assert!(!dir.join(JOURNAL_FILE).exists());
+        assert_eq!(store.read_state().unwrap().primary_project_id, Some(1));
+        store.begin().unwrap();
+        assert_eq!(store.allocate_id().unwrap(), 7);
+    }
+
+    #[test]
+    fn test_torn_journal_is_discarded() {
+        let dir = temp_dir("torn_journal");
+        fs::write(dir.join(FILE_PATH), "(old contents)").unwrap();
+        fs::write(dir.join(METADATA_FILE), "(next.id=4)").unwrap();
+
+        // Died while the journal itself was being written
+        let journal = Journal { last_id: Some(6), notes: Some(render_notebook(&sample_notebook())), ..Journal::default() };
+        let rendered = render_journal(&journal);
+        fs::write(dir.join(JOURNAL_FILE), &rendered[..rendered.len() - 3]).unwrap();
+
+        let mut store = TextStore::open(&dir).unwrap();
+        assert_eq!(fs::read_to_string(dir.join(FILE_PATH)).unwrap(), "(old contents)");
+        assert!(!dir.join(JOURNAL_FILE).exists());
+        store.begin().unwrap();
+        assert_eq!(store.allocate_id().unwrap(), 5);
+    }
+
+    #[test]
+    fn test_stale_lock_is_broken() {
+        let meta_path = temp_dir("stale_lock").join(METADATA_FILE);
+        let lock = fs::File::create(meta_path.with_extension("lock")).unwrap();
+        lock.set_modified(std::time::SystemTime::now() - STALE_LOCK * 2).unwrap();
+
+        let mut tx = Transaction::begin(&meta_path).unwrap();
+        assert_eq!(tx.allocate_id(), 1);
+    }
+
+    #[test]
+    fn test_text_store_writes_records() {
+        let dir = temp_dir("text_store");
+        let mut store = TextStore::open(&dir.join("nested")).unwrap();
+        let notebook = sample_notebook();
+
+        // Records are written one at a time, each project in its own chunk
+        let mut shed = notebook.notes[0].clone();
+        shed.id = 8;
+        shed.title = "Shed".to_string();
+        shed.objects.clear();
+        shed.milestones.clear();
+        let mut note = notebook.notes[0].clone();
+        note.objects.clear();
+        note.milestones.clear();
+        store.save_project(&note).unwrap();
+        store.save_project(&shed).unwrap();
+        store.save_object(&Object { items: Vec::new(), ..notebook.objects[0].clone() }).unwrap();
+        store.save_item(&notebook.items[0]).unwrap();
+        store.save_milestone(&notebook.milestones[0]).unwrap();
+        store.save_milestone(&notebook.milestones[1]).unwrap();
+
+        assert!(dir.join("nested").join(FILE_PATH).exists());
+        let loaded = store.load_notebook().unwrap();
+        assert_eq!(loaded.notes[0], notebook.notes[0]);
+        assert_eq!(loaded.objects, notebook.objects);
+        assert_eq!(loaded.milestones, notebook.milestones);
+        assert_eq!(loaded.notes[1], shed);
+
+        // Not written until the change is committed
+        store.begin().unwrap();
+        assert_eq!(store.allocate_id().unwrap(), 1);
+        store.abort();
+        store.begin().unwrap();
+        assert_eq!(store.allocate_id().unwrap(), 1);
+        store.commit().unwrap();
+        assert!(store.allocate_id().is_err());
+    }
+
+    #[test]
+    fn test_marker_text_in_fields_is_not_a_marker() {
+        let dir = temp_dir("marker_text");
+        fs::write(dir.join(METADATA_FILE), "(next.id=5)").unwrap();
+        let mut store = TextStore::open(&dir).unwrap();
+        let mut notebook = sample_notebook();
+        store.save_notebook(&notebook).unwrap();
+
+        notebook.notes[0].title = "(note.id1.active.start) Garden".to_string();
+        notebook.notes[0].description = "see (note.id1.end) here\n(note.id1.end)\n(note.id1.inactive.start)".to_string();
+        store.write_record(&notebook, NodeKind::Project, 1).unwrap();
+        assert_eq!(store.load_notebook().unwrap(), notebook);
+
+        // The next edit still finds the whole chunk
+        notebook.objects[0].title = "Raised beds".to_string();
+        store.write_record(&notebook, NodeKind::Object, 2).unwrap();
+        assert_eq!(store.load_notebook().unwrap(), notebook);
+        assert!(store.check().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_data_dir_resolution() {
+        let env = |vars: &'static [(&'static str, &'static str)]| {
+            move |key: &str| vars.iter().find(|(k, _)| *k == key).map(|(_, v)| v.to_string())
+        };
+        let all = env(&[("FUNKNOTES_HOME", "/notes"), ("XDG_DATA_HOME", "/xdg"), ("HOME", "/home/me")]);
+
+        assert_eq!(resolve_data_dir(Some("/flag"), all).unwrap(), PathBuf::from("/flag"));
+        assert_eq!(resolve_data_dir(None, all).unwrap(), PathBuf::from("/notes"));
+        assert_eq!(
+            resolve_data_dir(None, env(&[("FUNKNOTES_HOME", ""), ("XDG_DATA_HOME", "/xdg")])).unwrap(),
+            PathBuf::from("/xdg/funknotes")
+        );
+        assert_eq!(
+            resolve_data_dir(None, env(&[("XDG_DATA_HOME", "relative"), ("HOME", "/home/me")])).unwrap(),
+            PathBuf::from("/home/me/.local/share/funknotes")
+        );
+        assert!(resolve_data_dir(None, env(&[])).is_err());
+    }
+
+    #[test]
+    fn test_set_meta_value_keeps_other_keys() {
+        let mut meta: Metadata = vec![("next.id".to_string(), "4".to_string())];
+        set_meta_value(&mut meta, "current.project", Some("1".to_string()));
+        set_meta_value(&mut meta, "next.id", Some("5".to_string()));
+        assert_eq!(meta_value(&meta, "next.id"), Some("5"));
+        assert_eq!(meta_value(&meta, "current.project"), Some("1"));
+
+        set_meta_value(&mut meta, "current.project", None);
+        assert_eq!(meta, vec![("next.id".to_string(), "5".to_string())]);
+    }
+
+    #[test]
+    fn test_location_round_trip() {
+        for location in [
+            Location::default(),
+            Location { project_id: Some(3), object_id: None },
+            Location { project_id: Some(3), object_id: Some(12) },
+        ] {
+            assert_eq!(parse_location(&base_location(&location)), Some(location));
+        }
+        assert_eq!(parse_location("3-12"), None);
+    }
+
+    #[test]
+    fn test_parse_original_note_layout() {
+        // Notes written before objects/milestones were stored
+        let contents = r#"
+(note.id7.inactive.start)
+
+7.title: Old note
+
+7.description: Written by an early build
+
+7.date: 1764633600
+
+    ##### Milestones
+
+(note.id.7.end)
+"#;
+        let (upgraded, steps) = upgrade(contents).unwrap();
+        assert_eq!(steps.len(), 2);
+        assert!(upgraded.starts_with("(funknotes.format=2)\n"));
+        assert_eq!(upgraded.lines().count(), contents.lines().count() + 1);
+        assert!(upgraded.contains("(note.id7.end)") && !upgraded.contains("#####"));
+        assert_eq!(file_version(&upgraded).unwrap(), FORMAT_VERSION);
+
+        let notebook = parse_notebook(&upgraded).unwrap();
+        assert_eq!(notebook.notes.len(), 1);
+        let note = &notebook.notes[0];
+        assert_eq!(note.id, 7);
+        assert_eq!(note.title, "Old note");
+        assert!(!note.active);
+        assert!(note.objects.is_empty() && note.milestones.is_empty());
+        assert!(find_note_chunk(&upgraded, 7).is_ok());
+    }
+
+    #[test]
+    fn test_migrate_command() {
+        let dir = temp_dir("migrate");
+        let old = "\n(note.id3.active.start)\n\n3.title: Old\n\n    ##### Milestones\n\n(note.id.3.end)\n";
+        fs::write(dir.join(FILE_PATH), old).unwrap();
+        let mut store = TextStore::open(&dir).unwrap();
+
+        let report = store.migrate(true).unwrap();
+        assert_eq!((report.from, report.to, report.steps.len()), (0, FORMAT_VERSION, 2));
+        assert_eq!(fs::read_to_string(dir.join(FILE_PATH)).unwrap(), old);
+
+        let report = store.migrate(false).unwrap();
+        let backup = report.backup.unwrap();
+        assert_eq!(fs::read_to_string(&backup).unwrap(), old);
+        assert_eq!(backup, dir.join("all_notes.v0.bak"));
+        let migrated = fs::read_to_string(dir.join(FILE_PATH)).unwrap();
+        assert_eq!(file_version(&migrated).unwrap(), FORMAT_VERSION);
+        assert_eq!(store.load_notebook().unwrap().notes[0].title, "Old");
+        assert!(store.migrate(false).unwrap().steps.is_empty());
+
+        // Files from a newer build are left alone
+        fs::write(dir.join(FILE_PATH), "(funknotes.format=99)\n").unwrap();
+        assert!(store.load_notebook().is_err());
+        assert!(store.migrate(true).is_err());
+    }
+
+    #[test]
+    fn test_text_survives_any_characters() {
+        let mut notebook = sample_notebook();
+        notebook.notes[0].title = "Garden 2.title: not a title (note.id1.inactive.start)".to_string();
+        notebook.notes[0].description = "Line one\n\n(note.id1.end)\n  C:\\temp\tx\r".to_string();
+        notebook.objects[0].title = "(note.id1.active.start)".to_string();
+        notebook.items[0].text = "1-2-3.completed: true".to_string();
+
+        let rendered = render_notebook(&notebook);
+        assert_eq!(parse_notebook(&rendered).as_ref(), Ok(&notebook));
+
+        // Chunk edits read the file the same way the parser does
+        let (start, end) = find_note_chunk(&rendered, 1).unwrap();
+        assert!(rendered[start..].starts_with("(note.id1.active.start)\n"));
+        assert!(rendered[..end].ends_with("\n(note.id1.end)"));
+        assert_eq!(replace_note_chunk(&rendered, &notebook, 1).unwrap(), rendered);
+    }
+
+    #[test]
+    fn test_parse_errors_have_positions() {
+        let contents = "(funknotes.format=2)\n\
+                        (note.id1.active.start)\n\
+                        1.title: Fine\n\
+                        \x20   1.colour: red\n\
+                        1.date: soon\n\
+                        1.description: C:\\temp \\q\n\
+                        stray text\n\
+                        (object.id2.active.start)\n\
+                        (note.id1.end)\n\
+                        (item.id3.start)\n";
+        let errors = parse_notebook(contents).unwrap_err();
+        let found: Vec<(usize, usize)> = errors.iter().map(|e| (e.line, e.column)).collect();
+        assert_eq!(found, vec![(4, 7), (5, 9), (6, 24), (7, 1), (8, 1), (10, 1)]);
+        assert!(errors[0].message.contains("colour"));
+        assert!(errors[2].message.contains("\\q"));
+        assert!(errors[4].message.contains("isn't closed before line 9"));
+        assert_eq!(errors[0].to_string(), format!("line 4, column 7: {}", errors[0].message));
+
+        // Duplicate IDs and fields for the wrong record are caught too
+        let contents = "(note.id1.active.start)\n2.title: x\n(note.id1.end)\n(note.id1.active.start)\n(note.id1.end)\n";
+        let errors = parse_notebook(contents).unwrap_err();
+        assert_eq!(errors.iter().map(|e| e.line).collect::<Vec<_>>(), vec![2, 4, 5]);
+    }
+
+    #[test]
+    fn test_migrate_v1_folds_multi_line_text() {
+        let v1 = "(funknotes.format=1)\n\n(note.id1.active.start)\n\n1.title: C:\\notes\n\n\
+                  1.description: First line\nsecond line\n  third\n\n1.date: 5\n\n(note.id1.end)\n";
+        let (upgraded, steps) = upgrade(v1).unwrap();
+        assert_eq!(steps.len(), 1);
+        assert_eq!(upgraded.lines().count(), v1.lines().count());
+
+        let notebook = parse_notebook(&upgraded).unwrap();
+        assert_eq!(notebook.notes[0].title, "C:\\notes");
+        assert_eq!(notebook.notes[0].description, "First line\nsecond line\n  third");
+        assert_eq!(notebook.notes[0].created_on, 5);
+    }
+
+    #[test]
+    fn test_check_reports_problems() {
+        let dir = temp_dir("check");
+        let mut store = TextStore::open(&dir).unwrap();
+        assert!(store.check().unwrap().is_empty());
+
+        // Line 3 of an old file is line 4 once it has a header, but errors
+        // point at the file as it is
+        fs::write(dir.join(FILE_PATH), "(note.id1.active.start)\n1.title: x\n1.date: never\n(note.id1.end)\n").unwrap();
+        let problems = store.check().unwrap();
+        assert_eq!(problems.len(), 1);
+        assert!(problems[0].starts_with("all_notes.txt line 3, column 9:"), "{}", problems[0]);
+        let error = store.load_notebook().unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::InvalidData);
+
+        // Parsed fine, but the pieces don't fit
+        let mut notebook = sample_notebook();
+        notebook.milestones[0].target = MilestoneTarget::Item(42);
+        notebook.items[0].id = 2;
+        notebook.objects[0].items = vec![2];
+        store.save_notebook(&notebook).unwrap();
+        let problems = store.check().unwrap();
+        assert_eq!(problems.len(), 3, "{:?}", problems);
+        assert!(problems[0].contains("ID 2 is used by more than one record"));
+        assert!(problems[1].contains("tracks item 42"));
+        assert!(problems[2].contains("counter"));
+    }
 }
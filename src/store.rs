@@ -0,0 +1,647 @@
+use std::io;
+use std::path::PathBuf;
+
+use crate::timestamp::now_timestamp;
+use crate::types::*;
+
+// ====== THE STORE =====
+/// Somewhere notes are kept. A backend only has to load and save the whole
+/// notebook, hand out IDs and keep the navigation state. Loading, saving,
+/// updating and deleting single records is built on top of that, and backends
+/// that can write one record more cheaply than the whole notebook override
+/// write_record().
+pub trait Store {
+    fn load_notebook(&mut self) -> io::Result<Notebook>;
+    fn save_notebook(&mut self, notebook: &Notebook) -> io::Result<()>;
+
+    /// Write out the part of a changed notebook that holds one record.
+    fn write_record(&mut self, notebook: &Notebook, _kind: NodeKind, _id: usize) -> io::Result<()> {
+        self.save_notebook(notebook)
+    }
+
+    /// Lock the store for a change. IDs handed out by allocate_id() are only
+    /// kept if the change is committed; abort() forgets them and unlocks.
+    fn begin(&mut self) -> io::Result<()>;
+    fn allocate_id(&mut self) -> io::Result<usize>;
+    fn commit(&mut self) -> io::Result<()>;
+    fn abort(&mut self);
+
+    /// Which project and object are current, the history and the primary project.
+    fn read_state(&mut self) -> io::Result<FunkState>;
+    fn write_state(&mut self, state: &FunkState) -> io::Result<()>;
+
+    /// Upgrade what's saved to the newest format, or with dry_run just report
+    /// what that would involve. Backends without versions have nothing to do.
+    fn migrate(&mut self, _dry_run: bool) -> io::Result<MigrationReport> {
+        Ok(MigrationReport::default())
+    }
+
+    /// Every problem with what's saved, as messages for the user. Backends
+    /// that can say more about where a problem is override this.
+    fn check(&mut self) -> io::Result<Vec<String>> {
+        Ok(check_notebook(&self.load_notebook()?))
+    }
+
+    /// IDs of the records of a kind with exactly this title (or text, for items).
+    fn find_by_title(&mut self, kind: NodeKind, title: &str) -> io::Result<Vec<usize>> {
+        let notebook = self.load_notebook()?;
+        Ok(match kind {
+            NodeKind::Project => notebook.notes.iter().filter(|n| n.title == title).map(|n| n.id).collect(),
+            NodeKind::Object => notebook.objects.iter().filter(|o| o.title == title).map(|o| o.id).collect(),
+            NodeKind::Item => notebook.items.iter().filter(|i| i.text == title).map(|i| i.id).collect(),
+            NodeKind::Milestone => notebook.milestones.iter().filter(|m| m.title == title).map(|m| m.id).collect(),
+        })
+    }
+
+    // ===== Single records =====
+    fn load_project(&mut self, id: usize) -> io::Result<Option<Funknote>> {
+        load_record(self, id)
+    }
+
+    fn load_object(&mut self, id: usize) -> io::Result<Option<Object>> {
+        load_record(self, id)
+    }
+
+    fn load_item(&mut self, id: usize) -> io::Result<Option<Item>> {
+        load_record(self, id)
+    }
+
+    fn load_milestone(&mut self, id: usize) -> io::Result<Option<Milestone>> {
+        load_record(self, id)
+    }
+
+    /// Add a new project. Its ID comes from allocate_id().
+    fn save_project(&mut self, note: &Funknote) -> io::Result<()> {
+        save_record(self, note)
+    }
+
+    /// Add a new object to the project named by its project_id.
+    fn save_object(&mut self, object: &Object) -> io::Result<()> {
+        save_record(self, object)
+    }
+
+    /// Add a new item to the object named by its object_id.
+    fn save_item(&mut self, item: &Item) -> io::Result<()> {
+        save_record(self, item)
+    }
+
+    /// Add a new milestone to the project named by its project_id.
+    fn save_milestone(&mut self, milestone: &Milestone) -> io::Result<()> {
+        save_record(self, milestone)
+    }
+
+    fn update_project(&mut self, note: &Funknote) -> io::Result<()> {
+        update_record(self, note)
+    }
+
+    fn update_object(&mut self, object: &Object) -> io::Result<()> {
+        update_record(self, object)
+    }
+
+    fn update_item(&mut self, item: &Item) -> io::Result<()> {
+        update_record(self, item)
+    }
+
+    fn update_milestone(&mut self, milestone: &Milestone) -> io::Result<()> {
+        update_record(self, milestone)
+    }
+
+    /// Move a record, and everything under it, to the trash.
+    fn delete(&mut self, kind: NodeKind, id: usize) -> io::Result<TrashEntry> {
+        let mut notebook = self.load_notebook()?;
+        let contents = take_subtree(&mut notebook, kind, id);
+        if contents == Notebook::default() {
+            return Err(not_found(kind, id));
+        }
+
+        let entry = TrashEntry {
+            kind,
+            id,
+            deleted_on: now_timestamp(),
+            contents,
+        };
+        notebook.trash.push(entry.clone());
+        self.save_notebook(&notebook)?;
+        Ok(entry)
+    }
+}
+
+/// What migrate() did, or would do.
+#[derive(Debug, Default)]
+pub struct MigrationReport {
+    pub from: u32,                 // Format version found
+    pub to: u32,                   // Format version after migrating
+    pub steps: Vec<String>,        // One line per migration, oldest first
+    pub backup: Option<PathBuf>,   // Copy of the data from before migrating
+}
+
+// ====== SINGLE RECORDS =====
+// What the single-record operations need to know about each kind of record
+trait Record: Clone {
+    const KIND: NodeKind;
+    fn id(&self) -> usize;
+    fn all(notebook: &mut Notebook) -> &mut Vec<Self>;
+    // The record this one is listed under, if any
+    fn parent(&self) -> Option<(NodeKind, usize)>;
+    // Add this record to its parent's list of children
+    fn link(&self, notebook: &mut Notebook) -> io::Result<()>;
+}
+
+impl Record for Funknote {
+    const KIND: NodeKind = NodeKind::Project;
+
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn all(notebook: &mut Notebook) -> &mut Vec<Self> {
+        &mut notebook.notes
+    }
+
+    fn parent(&self) -> Option<(NodeKind, usize)> {
+        None
+    }
+
+    fn link(&self, _notebook: &mut Notebook) -> io::Result<()> {
+        Ok(()) // Projects are the top of the tree
+    }
+}
+
+impl Record for Object {
+    const KIND: NodeKind = NodeKind::Object;
+
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn all(notebook: &mut Notebook) -> &mut Vec<Self> {
+        &mut notebook.objects
+    }
+
+    fn parent(&self) -> Option<(NodeKind, usize)> {
+        Some((NodeKind::Project, self.project_id))
+    }
+
+    fn link(&self, notebook: &mut Notebook) -> io::Result<()> {
+        let project = notebook
+            .note_mut(self.project_id)
+            .ok_or_else(|| not_found(NodeKind::Project, self.project_id))?;
+        project.objects.push(self.id);
+        Ok(())
+    }
+}
+
+impl Record for Item {
+    const KIND: NodeKind = NodeKind::Item;
+
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn all(notebook: &mut Notebook) -> &mut Vec<Self> {
+        &mut notebook.items
+    }
+
+    fn parent(&self) -> Option<(NodeKind, usize)> {
+        Some((NodeKind::Object, self.object_id))
+    }
+
+    fn link(&self, notebook: &mut Notebook) -> io::Result<()> {
+        let object = notebook
+            .object_mut(self.object_id)
+            .ok_or_else(|| not_found(NodeKind::Object, self.object_id))?;
+        object.items.push(self.id);
+        Ok(())
+    }
+}
+
+impl Record for Milestone {
+    const KIND: NodeKind = NodeKind::Milestone;
+
+    fn id(&self) -> usize {
+        self.id
+    }
+
+    fn all(notebook: &mut Notebook) -> &mut Vec<Self> {
+        &mut notebook.milestones
+    }
+
+    fn parent(&self) -> Option<(NodeKind, usize)> {
+        Some((NodeKind::Project, self.project_id))
+    }
+
+    fn link(&self, notebook: &mut Notebook) -> io::Result<()> {
+        let project = notebook
+            .note_mut(self.project_id)
+            .ok_or_else(|| not_found(NodeKind::Project, self.project_id))?;
+        project.milestones.push(self.id);
+        Ok(())
+    }
+}
+
+fn load_record<S: Store + ?Sized, R: Record>(store: &mut S, id: usize) -> io::Result<Option<R>> {
+    let mut notebook = store.load_notebook()?;
+    Ok(R::all(&mut notebook).iter().find(|r| r.id() == id).cloned())
+}
+
+fn save_record<S: Store + ?Sized, R: Record>(store: &mut S, record: &R) -> io::Result<()> {
+    let mut notebook = store.load_notebook()?;
+    if R::all(&mut notebook).iter().any(|r| r.id() == record.id()) {
+        return Err(io::Error::new(
+            io::ErrorKind::AlreadyExists,
+            format!("{} {} already exists", R::KIND.name(), record.id()),
+        ));
+    }
+
+    record.link(&mut notebook)?;
+    R::all(&mut notebook).push(record.clone());
+    store.write_record(&notebook, R::KIND, record.id())
+}
+
+// Replaces the record with the same ID. Its parent has to stay the same.
+fn update_record<S: Store + ?Sized, R: Record>(store: &mut S, record: &R) -> io::Result<()> {
+    let mut notebook = store.load_notebook()?;
+    let existing = R::all(&mut notebook)
+        .iter_mut()
+        .find(|r| r.id() == record.id())
+        .ok_or_else(|| not_found(R::KIND, record.id()))?;
+    if existing.parent() != record.parent() {
+        return Err(parent_changed(R::KIND, record.id()));
+    }
+    *existing = record.clone();
+    store.write_record(&notebook, R::KIND, record.id())
+}
+
+fn not_found(kind: NodeKind, id: usize) -> io::Error {
+    io::Error::new(io::ErrorKind::NotFound, format!("{} {} doesn't exist", kind.name(), id))
+}
+
+// Updates only change a record in place; moving one would leave the old parent listing it
+pub(crate) fn parent_changed(kind: NodeKind, id: usize) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidInput, format!("{} {} can't be moved to another parent by an update", kind.name(), id))
+}
+
+// ====== THE TREE =====
+/// The project whose part of the store a record is written in.
+pub fn owning_project(notebook: &Notebook, kind: NodeKind, id: usize) -> Option<usize> {
+    match kind {
+        NodeKind::Project => Some(id),
+        NodeKind::Object => notebook.objects.iter().find(|o| o.id == id).map(|o| o.project_id),
+        NodeKind::Item => {
+            let object_id = notebook.items.iter().find(|i| i.id == id)?.object_id;
+            owning_project(notebook, NodeKind::Object, object_id)
+        }
+        NodeKind::Milestone => notebook.milestones.iter().find(|m| m.id == id).map(|m| m.project_id),
+    }
+}
+
+/// Pull a record and everything under it out of the notebook.
+/// Milestones tracking a removed object or item go with it.
+pub fn take_subtree(notebook: &mut Notebook, kind: NodeKind, id: usize) -> Notebook {
+    let note_ids: Vec<usize> = match kind {
+        NodeKind::Project => vec![id],
+        _ => Vec::new(),
+    };
+    let object_ids: Vec<usize> = match kind {
+        NodeKind::Project => notebook.objects.iter().filter(|o| o.project_id == id).map(|o| o.id).collect(),
+        NodeKind::Object => vec![id],
+        _ => Vec::new(),
+    };
+    let item_ids: Vec<usize> = match kind {
+        NodeKind::Item => vec![id],
+        _ => notebook.items.iter().filter(|i| object_ids.contains(&i.object_id)).map(|i| i.id).collect(),
+    };
+    let milestone_ids: Vec<usize> = notebook.milestones.iter()
+        .filter(|m| match kind {
+            NodeKind::Project => m.project_id == id,
+            NodeKind::Milestone => m.id == id,
+            _ => match m.target {
+                MilestoneTarget::Object(o) => object_ids.contains(&o),
+                MilestoneTarget::Item(i) => item_ids.contains(&i),
+                MilestoneTarget::Project(_) => false,
+            },
+        })
+        .map(|m| m.id)
+        .collect();
+
+    let taken = Notebook {
+        notes: take(&mut notebook.notes, |n| note_ids.contains(&n.id)),
+        objects: take(&mut notebook.objects, |o| object_ids.contains(&o.id)),
+        items: take(&mut notebook.items, |i| item_ids.contains(&i.id)),
+        milestones: take(&mut notebook.milestones, |m| milestone_ids.contains(&m.id)),
+        trash: Vec::new(),
+    };
+
+    // Live parents forget about the children they lost
+    for note in &mut notebook.notes {
+        note.objects.retain(|o| !object_ids.contains(o));
+        note.milestones.retain(|m| !milestone_ids.contains(m));
+    }
+    for object in &mut notebook.objects {
+        object.items.retain(|i| !item_ids.contains(i));
+    }
+    taken
+}
+
+/// Every ID in use, including the ones waiting in the trash.
+pub fn all_ids(notebook: &Notebook) -> impl Iterator<Item = usize> + '_ {
+    let live = notebook
+        .notes.iter().map(|n| n.id)
+        .chain(notebook.objects.iter().map(|o| o.id))
+        .chain(notebook.items.iter().map(|i| i.id))
+        .chain(notebook.milestones.iter().map(|m| m.id));
+    let trashed: Box<dyn Iterator<Item = usize>> =
+        Box::new(notebook.trash.iter().flat_map(|e| all_ids(&e.contents)));
+    live.chain(trashed)
+}
+
+/// Records that don't fit together: IDs used twice (one counter numbers every
+/// kind of record) and milestones tracking something that isn't there.
+pub fn check_notebook(notebook: &Notebook) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let mut ids: Vec<usize> = all_ids(notebook).collect();
+    ids.sort_unstable();
+    for pair in ids.windows(2).filter(|p| p[0] == p[1]) {
+        let message = format!("ID {} is used by more than one record", pair[0]);
+        if !problems.contains(&message) {
+            problems.push(message);
+        }
+    }
+
+    for milestone in &notebook.milestones {
+        let (kind, target) = match milestone.target {
+            MilestoneTarget::Project(id) => (NodeKind::Project, id),
+            MilestoneTarget::Object(id) => (NodeKind::Object, id),
+            MilestoneTarget::Item(id) => (NodeKind::Item, id),
+        };
+        let exists = match kind {
+            NodeKind::Project => notebook.notes.iter().any(|n| n.id == target),
+            NodeKind::Object => notebook.objects.iter().any(|o| o.id == target),
+            _ => notebook.items.iter().any(|i| i.id == target),
+        };
+        if !exists {
+            problems.push(format!(
+                "Milestone {} ('{}') tracks {} {}, which doesn't exist",
+                milestone.id, milestone.title, kind.name(), target
+            ));
+        }
+    }
+    problems
+}
+
+fn take<T>(records: &mut Vec<T>, pred: impl Fn(&T) -> bool) -> Vec<T> {
+    let (taken, kept) = std::mem::take(records).into_iter().partition(|r| pred(r));
+    *records = kept;
+    taken
+}
+
+// ====== IN MEMORY =====
+/// A store that keeps everything in memory, for tests.
+#[cfg(test)]
+#[derive(Debug, Default)]
+pub struct MemoryStore {
+    pub notebook: Notebook,
+    pub state: FunkState,
+    pub last_id: usize,          // The counter: last ID handed out
+    pending_id: Option<usize>,   // The counter inside a transaction
+}
+
+#[cfg(test)]
+impl MemoryStore {
+    pub fn with_notebook(notebook: Notebook) -> MemoryStore {
+        // Carry on numbering after the highest ID already in use
+        let last_id = all_ids(&notebook).max().unwrap_or(0);
+        MemoryStore {
+            notebook,
+            last_id,
+            ..MemoryStore::default()
+        }
+    }
+}
+
+#[cfg(test)]
+impl Store for MemoryStore {
+    fn load_notebook(&mut self) -> io::Result<Notebook> {
+        Ok(self.notebook.clone())
+    }
+
+    fn save_notebook(&mut self, notebook: &Notebook) -> io::Result<()> {
+        self.notebook = notebook.clone();
+        Ok(())
+    }
+
+    fn begin(&mut self) -> io::Result<()> {
+        if self.pending_id.is_some() {
+            return Err(io::Error::other("A change is already in progress"));
+        }
+        self.pending_id = Some(self.last_id);
+        Ok(())
+    }
+
+    fn allocate_id(&mut self) -> io::Result<usize> {
+        let id = self.pending_id.as_mut().ok_or_else(|| io::Error::other("No change in progress"))?;
+        *id += 1;
+        Ok(*id)
+    }
+
+    fn commit(&mut self) -> io::Result<()> {
+        self.last_id = self.pending_id.take().ok_or_else(|| io::Error::other("No change in progress"))?;
+        Ok(())
+    }
+
+    fn abort(&mut self) {
+        self.pending_id = None;
+    }
+
+    fn read_state(&mut self) -> io::Result<FunkState> {
+        Ok(self.state.clone())
+    }
+
+    fn write_state(&mut self, state: &FunkState) -> io::Result<()> {
+        self.state = state.clone();
+        Ok(())
+    }
+}
+
+// ====== TEST FIXTURES =====
+/// A small notebook and scratch directories shared by the tests.
+#[cfg(test)]
+pub mod fixtures {
+    use std::fs;
+    use std::path::PathBuf;
+
+    use crate::types::*;
+
+    /// One project with an object, a finished item in it, a milestone tracking
+    /// the item and one tracking the whole project. IDs run 1 to 5.
+    pub fn sample_notebook() -> Notebook {
+        Notebook {
+            notes: vec![Funknote {
+                id: 1,
+                title: "Garden".to_string(),
+                description: "Veggie patch".to_string(),
+                created_on: 1764633600,
+                active: true,
+                objects: vec![2],
+                milestones: vec![4, 5],
+            }],
+            objects: vec![Object {
+                id: 2,
+                project_id: 1,
+                title: "Beds".to_string(),
+                description: String::new(),
+                created_on: 1764633601,
+                active: true,
+                items: vec![3],
+            }],
+            items: vec![Item {
+                id: 3,
+                object_id: 2,
+                text: "Buy soil".to_string(),
+                created_on: 1764633602,
+                completed: true,
+                completed_on: Some(1764633700),
+            }],
+            milestones: vec![
+                Milestone {
+                    id: 4,
+                    project_id: 1,
+                    title: "Soil in".to_string(),
+                    description: String::new(),
+                    target_date: 1767225600,
+                    completed: false,
+                    completed_on: None,
+                    target: MilestoneTarget::Item(3),
+                },
+                Milestone {
+                    id: 5,
+                    project_id: 1,
+                    title: "Harvest".to_string(),
+                    description: String::new(),
+                    target_date: 0,
+                    completed: false,
+                    completed_on: None,
+                    target: MilestoneTarget::Project(1),
+                },
+            ],
+            trash: Vec::new(),
+        }
+    }
+
+    /// An item deleted from the sample's object, along with the milestone
+    /// tracking it. IDs 6 and 7.
+    pub fn sample_trash() -> TrashEntry {
+        TrashEntry {
+            kind: NodeKind::Item,
+            id: 6,
+            deleted_on: 1764633800,
+            contents: Notebook {
+                items: vec![Item {
+                    id: 6,
+                    object_id: 2,
+                    text: "Pull weeds".to_string(),
+                    created_on: 1764633603,
+                    completed: false,
+                    completed_on: None,
+                }],
+                milestones: vec![Milestone {
+                    id: 7,
+                    project_id: 1,
+                    title: "Sprouts".to_string(),
+                    description: "Tomatoes".to_string(),
+                    target_date: 0,
+                    completed: false,
+                    completed_on: None,
+                    target: MilestoneTarget::Item(6),
+                }],
+                ..Notebook::default()
+            },
+        }
+    }
+
+    /// A fresh, empty directory for one test.
+    pub fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("funk_notes_{}_{}", name, std::process::id()));
+        let _ = fs::remove_dir_all(&dir);
+        fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+}
+
+// ================== TESTS ===================
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::fixtures::sample_notebook;
+
+    #[test]
+    fn test_take_subtree_cascades_from_object() {
+        let mut notebook = sample_notebook();
+        let taken = take_subtree(&mut notebook, NodeKind::Object, 2);
+
+        assert_eq!(taken.objects.len(), 1);
+        assert_eq!(taken.items.len(), 1);
+        assert_eq!(taken.milestones.iter().map(|m| m.id).collect::<Vec<_>>(), vec![4]);
+        // The project keeps its own milestone and forgets the rest
+        assert!(notebook.objects.is_empty() && notebook.items.is_empty());
+        assert_eq!(notebook.notes[0].objects, Vec::<usize>::new());
+        assert_eq!(notebook.notes[0].milestones, vec![5]);
+    }
+
+    #[test]
+    fn test_single_record_operations() {
+        let mut store = MemoryStore::with_notebook(sample_notebook());
+        store.begin().unwrap();
+        let id = store.allocate_id().unwrap();
+        store.commit().unwrap();
+        assert_eq!(id, 6);
+
+        let item = Item {
+            id,
+            object_id: 2,
+            text: "Water".to_string(),
+            created_on: 0,
+            completed: false,
+            completed_on: None,
+        };
+        store.save_item(&item).unwrap();
+        assert_eq!(store.load_object(2).unwrap().unwrap().items, vec![3, 6]);
+        assert!(store.save_item(&item).is_err()); // Already there
+
+        let mut object = store.load_object(2).unwrap().unwrap();
+        object.title = "Raised beds".to_string();
+        store.update_object(&object).unwrap();
+        assert_eq!(store.load_object(2).unwrap().unwrap().title, "Raised beds");
+
+        // An update can't move a record: the old parent would still list it
+        let moved = Item { object_id: 99, ..item.clone() };
+        let error = store.update_item(&moved).unwrap_err();
+        assert_eq!(error.to_string(), "item 6 can't be moved to another parent by an update");
+        assert_eq!(store.load_item(6).unwrap().unwrap().object_id, 2);
+
+        let entry = store.delete(NodeKind::Object, 2).unwrap();
+        assert_eq!(entry.contents.items.len(), 2);
+        assert!(store.load_item(6).unwrap().is_none());
+        assert_eq!(store.load_notebook().unwrap().trash, vec![entry]);
+        assert!(store.delete(NodeKind::Object, 2).is_err());
+
+        // Children need a live parent
+        let orphan = Item { object_id: 99, ..item };
+        assert!(store.save_item(&orphan).is_err());
+    }
+
+    #[test]
+    fn test_aborted_ids_are_reused() {
+        let mut store = MemoryStore::default();
+        store.begin().unwrap();
+        assert_eq!(store.allocate_id().unwrap(), 1);
+        store.abort();
+        assert!(store.allocate_id().is_err()); // Only inside a change
+
+        store.begin().unwrap();
+        assert_eq!(store.allocate_id().unwrap(), 1);
+        store.commit().unwrap();
+        assert_eq!(store.last_id, 1);
+    }
+}
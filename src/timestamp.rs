@@ -27,7 +27,7 @@ fn unix_days_to_ymd(mut days: i64) -> (i32, u32, u32) {
     let m = mp + if mp < 10 { 3 } else { -9 };
     let year = y as i32 + (m <= 2) as i32;
 
-    (year as i32, m as u32, d as u32)
+    (year, m as u32, d as u32)
 }
 
 /// Returns a human-readable YYYY-MM-DD string for a timestamp
@@ -36,6 +36,33 @@ pub fn timestamp_to_string(ts: u64) -> String {
     format!("{year:04}-{month:02}-{day:02}")
 }
 
+/// Converts a (year, month, day) date to the UNIX timestamp of its midnight (UTC)
+pub fn ymd_to_unix(year: i32, month: u32, day: u32) -> u64 {
+    // The inverse of unix_days_to_ymd
+    let y = year as i64 - (month <= 2) as i64;
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month as i64 + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day as i64 - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    let days = era * 146097 + doe - 719468;
+    (days.max(0) * 86_400) as u64
+}
+
+/// Parses a YYYY-MM-DD date into a timestamp, if it's a real date
+pub fn string_to_timestamp(date: &str) -> Option<u64> {
+    let mut parts = date.split('-');
+    let (year, month, day) = (parts.next()?, parts.next()?, parts.next()?);
+    if parts.next().is_some() || year.len() != 4 || month.len() != 2 || day.len() != 2 {
+        return None;
+    }
+    let (year, month, day) = (year.parse::<i32>().ok()?, month.parse::<u32>().ok()?, day.parse::<u32>().ok()?);
+
+    // Only dates that come back out the same are real (no 31st of June)
+    let ts = ymd_to_unix(year, month, day);
+    (year >= 1970 && unix_to_ymd(ts) == (year, month, day)).then_some(ts)
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -51,9 +78,19 @@ mod tests {
     #[test]
     fn test_known_date() {
         // UNIX timestamp for 2025-12-02 00:00:00 UTC
-        let ts = 1764902400;
+        let ts = 1764633600;
         let s = timestamp_to_string(ts);
         assert_eq!(s, "2025-12-02");
     }
+
+    #[test]
+    fn test_parse_dates() {
+        assert_eq!(string_to_timestamp("2025-12-02"), Some(1764633600));
+        assert_eq!(string_to_timestamp("2024-02-29").map(timestamp_to_string), Some("2024-02-29".to_string()));
+        assert_eq!(string_to_timestamp("1970-01-01"), Some(0));
+        for bad in ["2025-02-29", "2025-13-01", "2025-6-01", "25-06-01", "2025-06-01-", "soon"] {
+            assert_eq!(string_to_timestamp(bad), None, "{}", bad);
+        }
+    }
 }
 
@@ -1,7 +1,5 @@
-use crate::timestamp::now_timestamp;
-
 // ============ The Hierarchy ============
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 pub struct Funknote {
     pub id: usize,
     pub title: String,
@@ -12,6 +10,7 @@ pub struct Funknote {
     pub milestones: Vec<usize>,   // IDs of milestones
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Object {
     pub id: usize,
     pub project_id: usize,        // Which project owns this?
@@ -22,6 +21,7 @@ pub struct Object {
     pub items: Vec<usize>,        // IDs of child items
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Item {
     pub id: usize,
     pub object_id: usize,         // Which object owns this?
@@ -31,6 +31,7 @@ pub struct Item {
     pub completed_on: Option<u64>,
 }
 
+#[derive(Debug, Clone, PartialEq)]
 pub struct Milestone {
     pub id: usize,
     pub project_id: usize,        // Always belongs to a project
@@ -50,10 +51,81 @@ pub enum MilestoneTarget {
     Item(usize),        // Milestone for a specific item
 }
 
+// The four kinds of record, as named in commands and in the notes file
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum NodeKind {
+    Project,
+    Object,
+    Item,
+    Milestone,
+}
+
+impl NodeKind {
+    pub fn name(&self) -> &'static str {
+        match self {
+            NodeKind::Project => "project",
+            NodeKind::Object => "object",
+            NodeKind::Item => "item",
+            NodeKind::Milestone => "milestone",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<NodeKind> {
+        match name {
+            "project" => Some(NodeKind::Project),
+            "object" => Some(NodeKind::Object),
+            "item" => Some(NodeKind::Item),
+            "milestone" => Some(NodeKind::Milestone),
+            _ => None,
+        }
+    }
+}
+
+// ============ The Whole Store ============
+// Everything in all_notes.txt, flattened into one list per type.
+// Parents keep the IDs of their children, children keep their parent's ID.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct Notebook {
+    pub notes: Vec<Funknote>,
+    pub objects: Vec<Object>,
+    pub items: Vec<Item>,
+    pub milestones: Vec<Milestone>,
+    pub trash: Vec<TrashEntry>,     // Deleted records, oldest first
+}
+
+// A deleted record plus everything that was deleted along with it
+#[derive(Debug, Clone, PartialEq)]
+pub struct TrashEntry {
+    pub kind: NodeKind,           // What was deleted
+    pub id: usize,                // ID of the deleted record
+    pub deleted_on: u64,
+    pub contents: Notebook,       // The record and its children
+}
+
+impl Notebook {
+    pub fn note_mut(&mut self, id: usize) -> Option<&mut Funknote> {
+        self.notes.iter_mut().find(|n| n.id == id)
+    }
+
+    pub fn object_mut(&mut self, id: usize) -> Option<&mut Object> {
+        self.objects.iter_mut().find(|o| o.id == id)
+    }
+}
+
 // ============ Application State ============
 
+#[derive(Debug, Clone, Default)]
 pub struct FunkState {
     pub current_project_id: Option<usize>,  // Which project are we in?
     pub current_object_id: Option<usize>,   // Which object are we in?
-    // Maybe later: pub history: Vec<usize>,  // Navigation history
+    pub history: Vec<Location>,             // Navigation history, oldest first
+    pub history_pos: usize,                 // Where in the history we are now
+    pub primary_project_id: Option<usize>,  // Default project when none is selected
+}
+
+// A place we've been: a project, and maybe an object in it
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct Location {
+    pub project_id: Option<usize>,
+    pub object_id: Option<usize>,
 }